@@ -0,0 +1,127 @@
+use avian2d::{math::*, prelude::*};
+use bevy::prelude::*;
+
+use crate::game::{spawn_controllable_character, MatchConfig, SpawnConfig, SpawnPoints};
+use crate::input::ActionWriter;
+use crate::player::{CharacterController, Grounded, PlayerAction, PlayerId};
+use crate::replay::not_replaying;
+use crate::rng::InputRng;
+use crate::weapons::WeaponSlot;
+
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (spawn_bot, ai_control.run_if(not_replaying)));
+    }
+}
+
+// Bots start chasing human players closer than this.
+const CHASE_RANGE: Scalar = 800.0;
+// Bots stop and shoot at human players closer than this.
+const FIRE_RANGE: Scalar = 350.0;
+// A bot trying to move slower than this is considered stuck on an obstacle and jumps.
+const STUCK_SPEED: Scalar = 20.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AiState {
+    // Wander around aimlessly.
+    Idle,
+    // Run towards the nearest human player.
+    Chase,
+    // Stand still and shoot at the nearest human player.
+    Fire,
+}
+
+// Drives a character with the same `PlayerAction`s a human player would send.
+#[derive(Component)]
+pub struct AiController {
+    pub state: AiState,
+    wander_direction: Scalar,
+    wander_timer: Timer,
+}
+
+impl Default for AiController {
+    fn default() -> Self {
+        Self {
+            state: AiState::Idle,
+            wander_direction: 1.0,
+            wander_timer: Timer::from_seconds(2.0, TimerMode::Repeating),
+        }
+    }
+}
+
+// Spawns a bot when F2 is pressed.
+fn spawn_bot(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    spawn_points: Res<SpawnPoints>,
+    match_config: Res<MatchConfig>,
+    characters: Query<&Transform, With<CharacterController>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F2) {
+        return;
+    }
+    let occupied: Vec<Vec2> = characters
+        .iter()
+        .map(|transform| transform.translation.truncate())
+        .collect();
+    let entity = spawn_controllable_character(
+        &mut commands,
+        &SpawnConfig {
+            position: spawn_points.pick(&occupied),
+            color: Color::srgb(0.5, 0.5, 0.5),
+            invulnerability_secs: match_config.invulnerability_secs,
+            ..default()
+        },
+    );
+    commands.entity(entity).insert(AiController::default());
+}
+
+fn ai_control(
+    time: Res<Time>,
+    mut rng: Local<InputRng>,
+    mut movement_event_writer: ActionWriter,
+    mut bots: Query<(Entity, &mut AiController, &Transform, &LinearVelocity, Has<Grounded>)>,
+    humans: Query<&Transform, (With<PlayerId>, Without<AiController>)>,
+) {
+    for (entity, mut bot, transform, velocity, grounded) in &mut bots {
+        let position = transform.translation.truncate();
+        let target = humans
+            .iter()
+            .map(|human| human.translation.truncate() - position)
+            .min_by(|a, b| a.length_squared().total_cmp(&b.length_squared()));
+
+        bot.state = match target.map(|offset| offset.length()) {
+            Some(distance) if distance <= FIRE_RANGE => AiState::Fire,
+            Some(distance) if distance <= CHASE_RANGE => AiState::Chase,
+            _ => AiState::Idle,
+        };
+
+        let direction = match (bot.state, target) {
+            (AiState::Fire, Some(offset)) => {
+                let aim = offset.normalize_or_zero();
+                movement_event_writer.send(PlayerAction::Aim(entity, aim.x, aim.y));
+                for slot in WeaponSlot::ALL {
+                    movement_event_writer.send(PlayerAction::Fire(entity, slot, 1.0));
+                }
+                0.0
+            }
+            (AiState::Chase, Some(offset)) => offset.x.signum(),
+            _ => {
+                if bot.wander_timer.tick(time.delta()).just_finished() {
+                    bot.wander_direction = if rng.chance(0.5) { 1.0 } else { -1.0 };
+                }
+                bot.wander_direction
+            }
+        };
+
+        if direction != 0.0 {
+            movement_event_writer.send(PlayerAction::Move(entity, direction));
+            // Hop over whatever is blocking the way
+            if grounded && velocity.x.abs() < STUCK_SPEED {
+                movement_event_writer.send(PlayerAction::Jump(entity));
+            }
+        }
+    }
+}
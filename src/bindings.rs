@@ -0,0 +1,341 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::player::InputDevice;
+use crate::round::GameState;
+
+pub struct BindingsPlugin;
+
+impl Plugin for BindingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InputBindings::load())
+            .add_systems(Startup, spawn_remap_prompt)
+            .add_systems(
+                Update,
+                (
+                    start_remapping.run_if(in_state(GameState::Lobby)),
+                    remap,
+                    show_remap_prompt,
+                )
+                    .chain(),
+            );
+    }
+}
+
+// Where the bindings are saved, relative to the working directory.
+const BINDINGS_PATH: &str = "bindings.json";
+
+// Bumped whenever saved bindings need migrating to new defaults, see `InputBindings::parse`.
+const BINDINGS_VERSION: u32 = 1;
+
+// The controls players can rebind, in the order the remapping screen asks for them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BoundAction {
+    Jump,
+    Fire,
+    Join,
+}
+
+impl BoundAction {
+    pub const ALL: [BoundAction; 3] = [BoundAction::Jump, BoundAction::Fire, BoundAction::Join];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BoundAction::Jump => "Jump",
+            BoundAction::Fire => "Fire",
+            BoundAction::Join => "Join",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeyboardBindings {
+    pub jump: KeyCode,
+    pub fire: KeyCode,
+    pub join: KeyCode,
+}
+
+impl KeyboardBindings {
+    pub fn get_mut(&mut self, action: BoundAction) -> &mut KeyCode {
+        match action {
+            BoundAction::Jump => &mut self.jump,
+            BoundAction::Fire => &mut self.fire,
+            BoundAction::Join => &mut self.join,
+        }
+    }
+}
+
+// Shared by every gamepad.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GamepadBindings {
+    pub jump: GamepadButton,
+    // Read as an analog value, so triggers give their pressure.
+    pub fire: GamepadButton,
+    pub join: GamepadButton,
+    // Fires the second gun of characters dual wielding, read like `fire`. Not on the
+    // remapping screen, but it can be changed in the saved bindings.
+    #[serde(default = "default_secondary_fire")]
+    pub secondary_fire: GamepadButton,
+    #[serde(default = "default_grapple")]
+    pub grapple: GamepadButton,
+    // Held to walk instead of run.
+    #[serde(default = "default_walk")]
+    pub walk: GamepadButton,
+}
+
+// Left gun on the left trigger, right gun on the right one.
+fn default_secondary_fire() -> GamepadButton {
+    GamepadButton::LeftTrigger2
+}
+
+// Both triggers fire guns, so the grapple goes on the bumper above the main one.
+fn default_grapple() -> GamepadButton {
+    GamepadButton::RightTrigger
+}
+
+// The left trigger is the second gun's, so walking is held on the stick doing the moving.
+fn default_walk() -> GamepadButton {
+    GamepadButton::LeftThumb
+}
+
+impl GamepadBindings {
+    pub fn get_mut(&mut self, action: BoundAction) -> &mut GamepadButton {
+        match action {
+            BoundAction::Jump => &mut self.jump,
+            BoundAction::Fire => &mut self.fire,
+            BoundAction::Join => &mut self.join,
+        }
+    }
+}
+
+// Which keys and buttons trigger the rebindable controls. Players change them on the
+// remapping screen in the lobby: F1 for the keyboard, Select for a gamepad.
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+pub struct InputBindings {
+    pub keyboard: KeyboardBindings,
+    pub gamepad: GamepadBindings,
+    // Bindings saved before versioning count as version 0.
+    #[serde(default)]
+    pub version: u32,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        Self {
+            keyboard: KeyboardBindings {
+                jump: KeyCode::Space,
+                fire: KeyCode::KeyF,
+                join: KeyCode::Enter,
+            },
+            gamepad: GamepadBindings {
+                jump: GamepadButton::South,
+                fire: GamepadButton::RightTrigger2,
+                join: GamepadButton::South,
+                secondary_fire: default_secondary_fire(),
+                grapple: default_grapple(),
+                walk: default_walk(),
+            },
+            version: BINDINGS_VERSION,
+        }
+    }
+}
+
+impl InputBindings {
+    // The saved bindings, or the defaults if there are none or they can't be read.
+    pub fn load() -> Self {
+        let Ok(json) = fs::read_to_string(BINDINGS_PATH) else {
+            return Self::default();
+        };
+        Self::parse(&json).unwrap_or_else(|error| {
+            warn!("Ignoring unreadable {BINDINGS_PATH}: {error}");
+            Self::default()
+        })
+    }
+
+    // Reads saved bindings, bringing ones saved by older versions up to date.
+    pub fn parse(json: &str) -> Result<Self, serde_json::Error> {
+        let mut bindings: Self = serde_json::from_str(json)?;
+        if bindings.version < 1 {
+            // Fire used to default to the right bumper, which only reads as fully
+            // pressed or not at all. The trigger behind it gives its pressure.
+            if bindings.gamepad.fire == GamepadButton::RightTrigger {
+                bindings.gamepad.fire = GamepadButton::RightTrigger2;
+            }
+        }
+        bindings.version = BINDINGS_VERSION;
+        Ok(bindings)
+    }
+
+    pub fn save(&self) {
+        let result = serde_json::to_string_pretty(self)
+            .map_err(|error| error.to_string())
+            .and_then(|json| fs::write(BINDINGS_PATH, json).map_err(|error| error.to_string()));
+        if let Err(error) = result {
+            warn!("Couldn't save {BINDINGS_PATH}: {error}");
+        }
+    }
+}
+
+// Present while a device's controls are being rebound, one action at a time.
+#[derive(Resource)]
+pub struct Remapping {
+    pub device: InputDevice,
+    step: usize,
+}
+
+impl Remapping {
+    pub fn action(&self) -> BoundAction {
+        BoundAction::ALL[self.step]
+    }
+}
+
+// Run condition for systems that shouldn't react to the keys being bound.
+pub fn not_remapping(remapping: Option<Res<Remapping>>) -> bool {
+    remapping.is_none()
+}
+
+#[derive(Component)]
+struct RemapPrompt;
+
+fn spawn_remap_prompt(mut commands: Commands) {
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(60.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                Text::default(),
+                TextFont {
+                    font_size: 24.0,
+                    ..default()
+                },
+                RemapPrompt,
+            ));
+        });
+}
+
+fn start_remapping(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<(Entity, &Gamepad)>,
+    remapping: Option<Res<Remapping>>,
+) {
+    if remapping.is_some() {
+        return;
+    }
+    let device = if keyboard_input.just_pressed(KeyCode::F1) {
+        Some(InputDevice::Keyboard)
+    } else {
+        gamepads
+            .iter()
+            .find(|(_, gamepad)| gamepad.just_pressed(GamepadButton::Select))
+            .map(|(entity, _)| InputDevice::Gamepad(entity))
+    };
+    if let Some(device) = device {
+        commands.insert_resource(Remapping { device, step: 0 });
+    }
+}
+
+// Binds the next key or button pressed on the device being remapped, saving the
+// bindings once every action has one. Escape cancels.
+fn remap(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    remapping: Option<ResMut<Remapping>>,
+    mut bindings: ResMut<InputBindings>,
+) {
+    let Some(mut remapping) = remapping else {
+        return;
+    };
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        commands.remove_resource::<Remapping>();
+        return;
+    }
+    let action = remapping.action();
+    match remapping.device {
+        InputDevice::Keyboard => {
+            // Skip the key that opened the remapping screen
+            let Some(key) = keyboard_input
+                .get_just_pressed()
+                .find(|key| **key != KeyCode::F1)
+            else {
+                return;
+            };
+            *bindings.keyboard.get_mut(action) = *key;
+        }
+        InputDevice::Gamepad(entity) => {
+            let Ok(gamepad) = gamepads.get(entity) else {
+                // Disconnected halfway through
+                commands.remove_resource::<Remapping>();
+                return;
+            };
+            let Some(button) = gamepad
+                .get_just_pressed()
+                .find(|button| **button != GamepadButton::Select)
+            else {
+                return;
+            };
+            *bindings.gamepad.get_mut(action) = *button;
+        }
+    }
+    remapping.step += 1;
+    if remapping.step >= BoundAction::ALL.len() {
+        bindings.save();
+        commands.remove_resource::<Remapping>();
+    }
+}
+
+fn show_remap_prompt(
+    remapping: Option<Res<Remapping>>,
+    mut prompts: Query<&mut Text, With<RemapPrompt>>,
+) {
+    let text = match &remapping {
+        Some(remapping) => {
+            let input = match remapping.device {
+                InputDevice::Keyboard => "a key",
+                InputDevice::Gamepad(_) => "a button",
+            };
+            format!(
+                "Press {input} for {} (Esc to cancel)",
+                remapping.action().name()
+            )
+        }
+        None => String::new(),
+    };
+    for mut prompt in &mut prompts {
+        if prompt.0 != text {
+            prompt.0.clone_from(&text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_bindings_fire_with_the_analog_trigger() {
+        let mut old = serde_json::to_value(InputBindings::default()).unwrap();
+        old["gamepad"]["fire"] = serde_json::to_value(GamepadButton::RightTrigger).unwrap();
+        old.as_object_mut().unwrap().remove("version");
+        let bindings = InputBindings::parse(&old.to_string()).unwrap();
+        assert_eq!(bindings.gamepad.fire, GamepadButton::RightTrigger2);
+        assert_eq!(bindings.version, BINDINGS_VERSION);
+    }
+
+    #[test]
+    fn current_bindings_are_kept_as_saved() {
+        let mut saved = InputBindings::default();
+        // Picked on the remapping screen on purpose
+        saved.gamepad.fire = GamepadButton::RightTrigger;
+        let json = serde_json::to_string(&saved).unwrap();
+        let bindings = InputBindings::parse(&json).unwrap();
+        assert_eq!(bindings.gamepad.fire, GamepadButton::RightTrigger);
+    }
+}
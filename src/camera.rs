@@ -0,0 +1,208 @@
+use bevy::prelude::*;
+use std::collections::HashSet;
+
+use crate::game::WorldBounds;
+use crate::player::{CharacterController, PlayerAssignments, KEYBOARD_ID};
+
+pub struct GameCameraPlugin;
+
+impl Plugin for GameCameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Spectating::default())
+            .insert_resource(CameraFraming::default())
+            .add_systems(Update, (spectator_camera, frame_characters).chain());
+    }
+}
+
+// How fast a spectator can fly the camera around, in pixels per second.
+const SPECTATOR_SPEED: f32 = 600.0;
+
+// How the camera keeps every character in view.
+#[derive(Resource)]
+pub struct CameraFraming {
+    // Projection scales the camera zooms between. Larger scales show more of the world.
+    pub min_scale: f32,
+    pub max_scale: f32,
+    // Space kept around the outermost characters, in pixels.
+    pub margin: f32,
+    // How quickly the camera catches up with the characters. Higher is snappier.
+    pub smoothing: f32,
+}
+
+impl Default for CameraFraming {
+    fn default() -> Self {
+        Self {
+            min_scale: 1.0,
+            max_scale: 2.5,
+            margin: 200.0,
+            smoothing: 3.0,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Spectating {
+    // Dead players (keyed like `PlayerAssignments`) waiting to respawn.
+    // Their stick, or the arrow keys and WASD for the keyboard player, pan the camera
+    // instead of moving a character.
+    pub players: HashSet<u32>,
+    // How far the spectators have panned the camera away from the living characters.
+    pub pan: Vec2,
+}
+
+// Pans the camera with the input of dead players only, so the living keep control of
+// their characters. The pan goes back to nothing once everyone is back in the game.
+fn spectator_camera(
+    time: Res<Time>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut spectating: ResMut<Spectating>,
+    assignments: Res<PlayerAssignments>,
+    gamepads: Query<(Entity, &Gamepad)>,
+) {
+    if spectating.players.is_empty() {
+        if spectating.pan != Vec2::ZERO {
+            spectating.pan = Vec2::ZERO;
+        }
+        return;
+    }
+    let mut direction = Vec2::ZERO;
+    for (entity, gamepad) in &gamepads {
+        let spectator = assignments
+            .gamepads
+            .get(&entity)
+            .is_some_and(|id| spectating.players.contains(id));
+        if spectator {
+            direction += gamepad.left_stick();
+        }
+    }
+    if spectating.players.contains(&KEYBOARD_ID) {
+        direction += keyboard_pan(&keyboard_input);
+    }
+    spectating.pan += direction * SPECTATOR_SPEED * time.delta_secs();
+}
+
+fn keyboard_pan(keyboard_input: &ButtonInput<KeyCode>) -> Vec2 {
+    let held = |keys: [KeyCode; 2]| {
+        if keyboard_input.any_pressed(keys) {
+            1.0
+        } else {
+            0.0
+        }
+    };
+    Vec2::new(
+        held([KeyCode::KeyD, KeyCode::ArrowRight]) - held([KeyCode::KeyA, KeyCode::ArrowLeft]),
+        held([KeyCode::KeyW, KeyCode::ArrowUp]) - held([KeyCode::KeyS, KeyCode::ArrowDown]),
+    )
+}
+
+// Pans and zooms the camera to keep all living characters in view, without showing
+// what's beyond the `WorldBounds`. Spectators can pan away from them.
+fn frame_characters(
+    time: Res<Time>,
+    framing: Res<CameraFraming>,
+    bounds: Res<WorldBounds>,
+    spectating: Res<Spectating>,
+    characters: Query<&Transform, (With<CharacterController>, Without<Camera2d>)>,
+    mut cameras: Query<(&mut Transform, &mut OrthographicProjection), With<Camera2d>>,
+) {
+    let Ok((mut camera, mut projection)) = cameras.get_single_mut() else {
+        return;
+    };
+    // The size of the view at a scale of 1
+    let viewport = projection.area.size() / projection.scale;
+    if viewport.min_element() <= 0.0 {
+        return;
+    }
+
+    let framed = characters
+        .iter()
+        .map(|transform| transform.translation.truncate())
+        .fold(None, |area: Option<Rect>, position| {
+            Some(area.map_or(Rect::from_center_size(position, Vec2::ZERO), |area| {
+                area.union_point(position)
+            }))
+        });
+    let (target, target_scale) = match framed {
+        Some(area) => {
+            let area = area.inflate(framing.margin);
+            let scale = (area.size() / viewport).max_element();
+            (area.center(), scale.clamp(framing.min_scale, framing.max_scale))
+        }
+        // Nobody to follow, rest on the spawn area
+        None => (Vec2::ZERO, framing.min_scale),
+    };
+
+    let target = target + spectating.pan;
+
+    let t = 1.0 - (-framing.smoothing * time.delta_secs()).exp();
+    projection.scale += (target_scale - projection.scale) * t;
+    let center = camera.translation.truncate().lerp(target, t);
+
+    // Keep the void out of view, unless the world is smaller than the view
+    let half_view = viewport * projection.scale / 2.0;
+    let (min, max) = (bounds.min + half_view, bounds.max - half_view);
+    let center = Vec2::new(
+        clamp_or_middle(center.x, min.x, max.x),
+        clamp_or_middle(center.y, min.y, max.y),
+    );
+    camera.translation = center.extend(camera.translation.z);
+}
+
+// Clamps `value` to `min..=max`, or picks the middle of the two if they're the wrong way around.
+fn clamp_or_middle(value: f32, min: f32, max: f32) -> f32 {
+    if min <= max {
+        value.clamp(min, max)
+    } else {
+        (min + max) / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use std::time::Duration;
+
+    use super::*;
+
+    // Runs the camera for half a second with `character` alive, if any, while `spectators`
+    // are dead and the right arrow key is held. Returns where the camera ends up.
+    fn camera_after(character: Option<Vec2>, spectators: &[u32]) -> Vec2 {
+        let mut world = World::new();
+        let mut time = Time::<()>::default();
+        time.advance_by(Duration::from_secs_f32(0.5));
+        world.insert_resource(time);
+        let mut keyboard_input = ButtonInput::<KeyCode>::default();
+        keyboard_input.press(KeyCode::ArrowRight);
+        world.insert_resource(keyboard_input);
+        world.init_resource::<CameraFraming>();
+        world.init_resource::<WorldBounds>();
+        world.init_resource::<PlayerAssignments>();
+        world.insert_resource(Spectating {
+            players: spectators.iter().copied().collect(),
+            ..default()
+        });
+        if let Some(position) = character {
+            world.spawn((CharacterController, Transform::from_translation(position.extend(0.0))));
+        }
+        let camera = world
+            .spawn((Camera2d, Transform::default(), OrthographicProjection::default_2d()))
+            .id();
+        world.run_system_once((spectator_camera, frame_characters).chain()).unwrap();
+        world.get::<Transform>(camera).unwrap().translation.truncate()
+    }
+
+    #[test]
+    fn living_characters_stay_framed_while_others_spectate() {
+        let camera = camera_after(Some(Vec2::new(0.0, 300.0)), &[1]);
+        assert!(camera.y > 0.0);
+        // A gamepad player is dead, so the living keyboard player's keys don't pan
+        assert_eq!(camera.x, 0.0);
+    }
+
+    #[test]
+    fn dead_keyboard_players_pan_with_the_arrow_keys() {
+        assert!(camera_after(Some(Vec2::ZERO), &[KEYBOARD_ID]).x > 0.0);
+        assert!(camera_after(None, &[KEYBOARD_ID]).x > 0.0);
+        assert_eq!(camera_after(None, &[]), Vec2::ZERO);
+    }
+}
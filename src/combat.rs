@@ -0,0 +1,1058 @@
+use avian2d::prelude::*;
+use bevy::{
+    ecs::{query::QueryData, system::SystemParam},
+    prelude::*,
+    sprite::AlphaMode2d,
+};
+use std::collections::HashSet;
+
+use crate::camera::Spectating;
+use crate::debug::SandboxSettings;
+use crate::game::{kill_character, GameMode, MatchConfig};
+use crate::gravity::LocalGravity;
+use crate::layers;
+use crate::player::{
+    CharacterController, Grounded, PlayerAction, PlayerAssignments, PlayerId, Stomp, Stomping,
+};
+use crate::rng::GameRng;
+use crate::weapons::{PlacedMines, Projectile, Sticky, MAX_PLACED_MINES};
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageEvent>()
+            .add_event::<DeathEvent>()
+            .add_event::<Explosion>()
+            .add_event::<GunBash>()
+            .configure_sets(FixedUpdate, (CombatSet::Damage, CombatSet::Cleanup).chain())
+            // Hits push things around and deaths draw from the `GameRng`, so combat runs on
+            // the fixed time step along with movement
+            .add_systems(
+                FixedUpdate,
+                (
+                    tick_invulnerability,
+                    expire_last_hits,
+                    stick_mines,
+                    projectile_damage,
+                    detonate_mines,
+                    apply_explosions,
+                    land_gun_bashes,
+                    land_stomps,
+                    head_stomps,
+                    reset_fall_speed,
+                )
+                    .chain()
+                    .in_set(CombatSet::Damage),
+            )
+            .add_systems(FixedUpdate, track_fall_speed.after(CombatSet::Damage))
+            .add_systems(
+                FixedUpdate,
+                (
+                    spawn_ragdolls,
+                    despawn_dead,
+                    detonate_barrels,
+                    destroy_destructibles,
+                    expire_debris,
+                    fade_explosions,
+                )
+                    .chain()
+                    .in_set(CombatSet::Cleanup),
+            );
+    }
+}
+
+// Systems reacting to damage and death events should run between these two sets,
+// so that the entities involved still exist.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CombatSet {
+    // Detects hits and sends `DamageEvent`s and `DeathEvent`s.
+    Damage,
+    // Removes dead entities from the world.
+    Cleanup,
+}
+
+// Anything that can take damage: characters, crates and so on.
+#[derive(Component)]
+pub struct Damageable {
+    pub health: f32,
+    pub max: f32,
+}
+
+impl Damageable {
+    pub fn new(max: f32) -> Self {
+        Self { health: max, max }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.health <= 0.0
+    }
+}
+
+// Extra protection that soaks up hits before they reach `Damageable::health`.
+// Whatever a hit deals beyond what's left of the shield spills over into health.
+#[derive(Component)]
+pub struct Shield {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Shield {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    // Takes as much of `amount` as the shield has left, returning the rest.
+    pub fn absorb(&mut self, amount: f32) -> f32 {
+        let absorbed = amount.min(self.current).max(0.0);
+        self.current -= absorbed;
+        amount - absorbed
+    }
+
+    pub fn is_depleted(&self) -> bool {
+        self.current <= 0.0
+    }
+
+    pub fn fraction(&self) -> f32 {
+        (self.current / self.max).clamp(0.0, 1.0)
+    }
+}
+
+// A piece of cover that breaks into debris once it's destroyed.
+#[derive(Component)]
+pub struct Destructible;
+
+// A short-lived chunk of a destroyed `Destructible`, or the body a character leaves behind.
+#[derive(Component)]
+pub struct Debris {
+    pub timer: Timer,
+}
+
+// A `Destructible` that explodes when destroyed, possibly setting off the barrels around it.
+#[derive(Component)]
+pub struct ExplosiveBarrel {
+    pub radius: f32,
+    pub damage: f32,
+}
+
+impl Default for ExplosiveBarrel {
+    fn default() -> Self {
+        Self {
+            radius: 120.0,
+            damage: 40.0,
+        }
+    }
+}
+
+// A blast that damages everything within `radius` of `center`, such as a grenade
+// or a barrel going off. `source` is the character responsible for it, if any.
+#[derive(Event, Clone, Copy)]
+pub struct Explosion {
+    pub center: Vec2,
+    pub radius: f32,
+    pub damage: f32,
+    pub source: Option<Entity>,
+}
+
+// A swing of an empty gun by `attacker`, from `origin` along `direction`.
+#[derive(Event, Clone, Copy)]
+pub struct GunBash {
+    pub attacker: Entity,
+    pub origin: Vec2,
+    pub direction: Vec2,
+}
+
+// How far in front of the attacker a gun bash reaches, and how wide it swings, as the
+// cosine of the largest angle off the aim.
+const GUN_BASH_RANGE: f32 = 60.0;
+const GUN_BASH_ARC: f32 = 0.5;
+const GUN_BASH_DAMAGE: f32 = 15.0;
+// How hard a gun bash shoves what it hits, as a change in velocity.
+const GUN_BASH_KNOCKBACK: f32 = 350.0;
+
+// How hard explosions push what they hit, in pixels per second per point of damage, at
+// the center. The push falls off to nothing at the edge of the blast.
+const EXPLOSION_KNOCKBACK: f32 = 12.0;
+
+// The visible flash of an explosion, fading out with the timer.
+#[derive(Component)]
+pub struct ExplosionFlash {
+    pub timer: Timer,
+}
+
+// How many pieces of debris a destroyed `Destructible` breaks into.
+const DEBRIS_PIECES: usize = 6;
+
+// Protects freshly spawned characters from damage until the timer runs out.
+#[derive(Component)]
+pub struct Invulnerable {
+    pub timer: Timer,
+}
+
+impl Invulnerable {
+    pub fn new(seconds: f32) -> Self {
+        Self {
+            timer: Timer::from_seconds(seconds, TimerMode::Once),
+        }
+    }
+}
+
+// How many times per second an invulnerable character blinks.
+const INVULNERABILITY_BLINK_RATE: f32 = 8.0;
+
+// The character that last hit this one. Credited with the kill if this one dies
+// without a killer (e.g. by falling out of the world) before the timer runs out.
+#[derive(Component)]
+pub struct LastHitBy {
+    pub attacker: Entity,
+    pub timer: Timer,
+}
+
+impl LastHitBy {
+    pub fn new(attacker: Entity) -> Self {
+        Self {
+            attacker,
+            timer: Timer::from_seconds(LAST_HIT_SECS, TimerMode::Once),
+        }
+    }
+}
+
+// How long a hit counts towards a later kill, in seconds.
+const LAST_HIT_SECS: f32 = 5.0;
+
+// How hard a hit knocks a character back in sumo, in pixels per second per point of damage.
+const SUMO_KNOCKBACK: f32 = 40.0;
+
+// How much harder characters caught in their own blast are pushed than anyone else.
+// Strong enough to rocket jump with a grenade.
+const SELF_KNOCKBACK_SCALE: f32 = 1.5;
+// The share of the damage characters take from their own blasts.
+const SELF_DAMAGE_SCALE: f32 = 0.25;
+
+// Everything a hit can affect on its target.
+#[derive(QueryData)]
+#[query_data(mutable)]
+pub struct HitTarget {
+    pub entity: Entity,
+    pub damageable: &'static mut Damageable,
+    pub transform: &'static Transform,
+    pub velocity: Option<&'static mut LinearVelocity>,
+    pub shield: Option<&'static mut Shield>,
+    pub gravity: Option<&'static LocalGravity>,
+    pub character: Has<CharacterController>,
+    pub player: Has<PlayerId>,
+}
+
+// An event sent whenever an entity is hurt. `amount` is the health it lost and
+// `absorbed` the part of the hit its shield soaked up instead. `source` is the character
+// responsible for the damage, if any.
+#[derive(Event)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: f32,
+    pub absorbed: f32,
+    pub source: Option<Entity>,
+}
+
+impl DamageEvent {
+    // The whole hit, whether it reached health or not.
+    pub fn total(&self) -> f32 {
+        self.amount + self.absorbed
+    }
+}
+
+// An event sent once when a `Damageable` runs out of health. `killer` is the
+// character that dealt the final blow, if any.
+// Systems reacting to deaths (scoring, respawning, debris, ...) read this instead of polling health.
+#[derive(Event)]
+pub struct DeathEvent {
+    pub victim: Entity,
+    pub killer: Option<Entity>,
+}
+
+// The single entry point for dealing damage. Every hit source goes through
+// this so that damage and death events are always sent consistently.
+#[derive(SystemParam)]
+pub struct DamageWriter<'w, 's> {
+    commands: Commands<'w, 's>,
+    damage_event_writer: EventWriter<'w, DamageEvent>,
+    death_event_writer: EventWriter<'w, DeathEvent>,
+    match_config: Res<'w, MatchConfig>,
+    sandbox: Res<'w, SandboxSettings>,
+}
+
+impl DamageWriter<'_, '_> {
+    // Hits `target` for `amount` on behalf of `source`, pushing it along `direction` by
+    // `knockback`, a change in velocity.
+    // In sumo, characters are only knocked back and never lose health.
+    // Players in sandbox god mode shrug hits off entirely. Characters only hit themselves
+    // (e.g. with their own grenades) if `MatchConfig::self_damage` is on.
+    pub fn hit(
+        &mut self,
+        target: &mut HitTargetItem,
+        amount: f32,
+        direction: Vec2,
+        knockback: f32,
+        source: Option<Entity>,
+    ) {
+        if self.sandbox.god_mode && target.player {
+            return;
+        }
+        if source == Some(target.entity) {
+            if !self.match_config.self_damage {
+                return;
+            }
+            Self::push(target, direction, knockback * SELF_KNOCKBACK_SCALE);
+            if self.match_config.mode == GameMode::Sumo {
+                return;
+            }
+            let amount = amount * SELF_DAMAGE_SCALE;
+            self.apply_shielded(target, amount, source);
+            return;
+        }
+        if let Some(attacker) = source {
+            self.commands
+                .entity(target.entity)
+                .try_insert(LastHitBy::new(attacker));
+        }
+        // Sumo hits push at least as hard as their damage, but never twice
+        if self.match_config.mode == GameMode::Sumo && target.character {
+            Self::push(target, direction, knockback.max(amount * SUMO_KNOCKBACK));
+            return;
+        }
+        Self::push(target, direction, knockback);
+        self.apply_shielded(target, amount, source);
+    }
+
+    // Hits with no direction, e.g. a blast right at the target's feet, push it up.
+    fn push(target: &mut HitTargetItem, direction: Vec2, knockback: f32) {
+        let direction = direction
+            .try_normalize()
+            .unwrap_or_else(|| up_for(target.gravity));
+        if let Some(velocity) = &mut target.velocity {
+            velocity.0 += direction * knockback;
+        }
+    }
+
+    // Lets the target's shield, if any, soak up the hit before it reaches health.
+    fn apply_shielded(&mut self, target: &mut HitTargetItem, amount: f32, source: Option<Entity>) {
+        let remaining = match &mut target.shield {
+            Some(shield) => shield.absorb(amount),
+            None => amount,
+        };
+        let absorbed = amount - remaining;
+        self.deal(target.entity, &mut target.damageable, remaining, absorbed, source);
+    }
+
+    pub fn apply(
+        &mut self,
+        target: Entity,
+        damageable: &mut Damageable,
+        amount: f32,
+        source: Option<Entity>,
+    ) {
+        self.deal(target, damageable, amount, 0.0, source);
+    }
+
+    fn deal(
+        &mut self,
+        target: Entity,
+        damageable: &mut Damageable,
+        amount: f32,
+        absorbed: f32,
+        source: Option<Entity>,
+    ) {
+        if damageable.is_dead() {
+            return;
+        }
+        damageable.health -= amount;
+        self.damage_event_writer.send(DamageEvent {
+            target,
+            amount,
+            absorbed,
+            source,
+        });
+        if damageable.is_dead() {
+            self.death_event_writer.send(DeathEvent {
+                victim: target,
+                killer: source,
+            });
+        }
+    }
+
+    // Deals exactly enough damage to kill the target.
+    pub fn kill(&mut self, target: Entity, damageable: &mut Damageable, source: Option<Entity>) {
+        let amount = damageable.health;
+        self.apply(target, damageable, amount, source);
+    }
+}
+
+// Counts down invulnerability and blinks the character while it lasts.
+fn tick_invulnerability(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut query: Query<(Entity, &mut Invulnerable, Option<&MeshMaterial2d<ColorMaterial>>)>,
+) {
+    for (entity, mut invulnerable, material) in &mut query {
+        invulnerable.timer.tick(time.delta());
+        let finished = invulnerable.timer.finished();
+        if let Some(material) = material.and_then(|handle| materials.get_mut(&handle.0)) {
+            let blink = (invulnerable.timer.elapsed_secs() * INVULNERABILITY_BLINK_RATE) as u32 % 2;
+            let alpha = if finished || blink == 0 { 1.0 } else { 0.3 };
+            // Opaque materials ignore alpha, so blend while the character blinks
+            material.alpha_mode = if finished { AlphaMode2d::Opaque } else { AlphaMode2d::Blend };
+            material.color.set_alpha(alpha);
+        }
+        if finished {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+}
+
+fn expire_last_hits(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut last_hits: Query<(Entity, &mut LastHitBy)>,
+) {
+    for (entity, mut last_hit_by) in &mut last_hits {
+        if last_hit_by.timer.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<LastHitBy>();
+        }
+    }
+}
+
+// Applies projectile damage to whatever the projectile hit first and removes the projectile.
+fn projectile_damage(
+    mut commands: Commands,
+    mut collision_event_reader: EventReader<CollisionStarted>,
+    projectiles: Query<(&Projectile, &Transform, &LinearVelocity), Without<Sticky>>,
+    mut targets: Query<HitTarget, (Without<Invulnerable>, Without<Projectile>)>,
+    sensors: Query<(), With<Sensor>>,
+    mut damage: DamageWriter,
+    mut explosion_writer: EventWriter<Explosion>,
+) {
+    let mut spent = HashSet::new();
+    for CollisionStarted(entity1, entity2) in collision_event_reader.read() {
+        for (projectile_entity, target) in [(*entity1, *entity2), (*entity2, *entity1)] {
+            let Ok((projectile, transform, velocity)) = projectiles.get(projectile_entity) else {
+                continue;
+            };
+            // Layers can't tell the shooter apart from other characters. Trigger zones
+            // are on the world layer so that force zones can push projectiles, but
+            // they're flown through, not hit
+            if target == projectile.owner
+                || spent.contains(&projectile_entity)
+                || sensors.contains(target)
+            {
+                continue;
+            }
+            // Explosive projectiles go off on whatever they touch first
+            if let Some(radius) = projectile.blast_radius {
+                explosion_writer.send(Explosion {
+                    center: transform.translation.truncate(),
+                    radius,
+                    damage: projectile.damage,
+                    source: Some(projectile.owner),
+                });
+                spent.insert(projectile_entity);
+                commands.entity(projectile_entity).despawn();
+                continue;
+            }
+            let Ok(mut target) = targets.get_mut(target) else {
+                continue;
+            };
+            let amount = projectile.damage_at(transform.translation.truncate());
+            damage.hit(&mut target, amount, velocity.0, 0.0, Some(projectile.owner));
+            spent.insert(projectile_entity);
+            commands.entity(projectile_entity).despawn();
+        }
+    }
+}
+
+// Sticks mines to the ground they land on and arms them. Mines bounce off anything that
+// moves, characters included.
+fn stick_mines(
+    mut commands: Commands,
+    mut collision_event_reader: EventReader<CollisionStarted>,
+    mut mines: Query<(&mut Sticky, &mut Projectile, &mut LinearVelocity)>,
+    bodies: Query<&RigidBody, Without<Sensor>>,
+    mut owners: Query<&mut PlacedMines>,
+) {
+    for CollisionStarted(entity1, entity2) in collision_event_reader.read() {
+        for (mine, surface) in [(*entity1, *entity2), (*entity2, *entity1)] {
+            let Ok((mut sticky, mut projectile, mut velocity)) = mines.get_mut(mine) else {
+                continue;
+            };
+            let on_ground = bodies.get(surface).is_ok_and(|body| !body.is_dynamic());
+            if sticky.armed || !on_ground {
+                continue;
+            }
+            sticky.armed = true;
+            // Laid mines wait for their owner however long it takes
+            projectile.lifetime = f32::INFINITY;
+            velocity.0 = Vec2::ZERO;
+            // Characters walk over laid mines rather than tripping on them
+            commands.entity(mine).insert((RigidBody::Static, Sensor));
+            if let Ok(mut placed) = owners.get_mut(projectile.owner) {
+                placed.0.push_back(mine);
+                while placed.0.len() > MAX_PLACED_MINES {
+                    if let Some(oldest) = placed.0.pop_front() {
+                        commands.entity(oldest).despawn();
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Sets off the mines of everyone who asked to.
+fn detonate_mines(
+    mut commands: Commands,
+    mut movement_event_reader: EventReader<PlayerAction>,
+    mut owners: Query<&mut PlacedMines>,
+    mines: Query<(&Projectile, &Transform), With<Sticky>>,
+    mut explosion_writer: EventWriter<Explosion>,
+) {
+    for event in movement_event_reader.read() {
+        let PlayerAction::Detonate(owner) = event else {
+            continue;
+        };
+        let Ok(mut placed) = owners.get_mut(*owner) else {
+            continue;
+        };
+        for mine in placed.0.drain(..) {
+            // Mines can be gone already, e.g. evicted by the projectile budget
+            let Ok((projectile, transform)) = mines.get(mine) else {
+                continue;
+            };
+            explosion_writer.send(Explosion {
+                center: transform.translation.truncate(),
+                radius: projectile.blast_radius.unwrap_or_default(),
+                damage: projectile.damage,
+                source: Some(*owner),
+            });
+            commands.entity(mine).despawn();
+        }
+    }
+}
+
+// Hits every target within `radius` of `center` on behalf of `source`, pushing them outwards
+// the harder the closer they are.
+pub fn explode<'a>(
+    center: Vec2,
+    radius: f32,
+    amount: f32,
+    source: Option<Entity>,
+    targets: impl IntoIterator<Item = HitTargetItem<'a>>,
+    damage: &mut DamageWriter,
+) {
+    for mut target in targets {
+        let offset = target.transform.translation.truncate() - center;
+        let distance = offset.length();
+        if distance <= radius {
+            let falloff = 1.0 - distance / radius.max(f32::EPSILON);
+            let knockback = amount * EXPLOSION_KNOCKBACK * falloff;
+            damage.hit(&mut target, amount, offset, knockback, source);
+        }
+    }
+}
+
+// Hits everything in front of the attacker within reach of a gun bash.
+fn land_gun_bashes(
+    mut bash_reader: EventReader<GunBash>,
+    mut targets: Query<HitTarget, Without<Invulnerable>>,
+    mut damage: DamageWriter,
+) {
+    for bash in bash_reader.read() {
+        let direction = bash.direction.normalize_or_zero();
+        for mut target in &mut targets {
+            if target.entity == bash.attacker {
+                continue;
+            }
+            let offset = target.transform.translation.truncate() - bash.origin;
+            let in_reach = offset.length() <= GUN_BASH_RANGE;
+            if !in_reach || offset.normalize_or_zero().dot(direction) < GUN_BASH_ARC {
+                continue;
+            }
+            let attacker = Some(bash.attacker);
+            damage.hit(&mut target, GUN_BASH_DAMAGE, direction, GUN_BASH_KNOCKBACK, attacker);
+        }
+    }
+}
+
+fn apply_explosions(
+    mut commands: Commands,
+    mut explosion_reader: EventReader<Explosion>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut targets: Query<HitTarget, Without<Invulnerable>>,
+    mut damage: DamageWriter,
+) {
+    for explosion in explosion_reader.read() {
+        explode(
+            explosion.center,
+            explosion.radius,
+            explosion.damage,
+            explosion.source,
+            &mut targets,
+            &mut damage,
+        );
+        commands.spawn((
+            Mesh2d(meshes.add(Circle::new(explosion.radius))),
+            MeshMaterial2d(materials.add(ColorMaterial {
+                color: Color::srgba(1.0, 0.6, 0.1, 0.6),
+                alpha_mode: AlphaMode2d::Blend,
+                ..default()
+            })),
+            Transform::from_translation(explosion.center.extend(1.0)),
+            ExplosionFlash {
+                timer: Timer::from_seconds(0.3, TimerMode::Once),
+            },
+        ));
+    }
+}
+
+// Ends stomps that have hit the ground, damaging everything around the landing spot.
+fn land_stomps(
+    mut commands: Commands,
+    landed: Query<(Entity, &Stomp, &Transform), (With<Stomping>, With<Grounded>)>,
+    mut targets: Query<HitTarget, (Without<Stomping>, Without<Invulnerable>)>,
+    mut damage: DamageWriter,
+) {
+    for (entity, stomp, transform) in &landed {
+        let center = transform.translation.truncate();
+        explode(center, stomp.radius, stomp.damage, Some(entity), &mut targets, &mut damage);
+        commands.entity(entity).remove::<Stomping>();
+    }
+}
+
+// Lets a character hurt others by landing on their heads: falling onto a character
+// from above at `min_speed` or faster deals `damage` to it and bounces the attacker
+// back up at `bounce`.
+#[derive(Component)]
+pub struct StompAttack {
+    pub damage: f32,
+    pub bounce: f32,
+    pub min_speed: f32,
+    // The fastest the character fell before any physics step since `head_stomps` last
+    // ran. By the time a collision is reported the landing has already stopped the fall.
+    fall_speed: f32,
+}
+
+impl Default for StompAttack {
+    fn default() -> Self {
+        Self {
+            damage: 25.0,
+            bounce: 600.0,
+            min_speed: 200.0,
+            fall_speed: 0.0,
+        }
+    }
+}
+
+// How closely the attacker has to be lined up above its victim, as the cosine of the
+// angle between "up" and the direction from the victim to the attacker.
+const HEAD_STOMP_ALIGNMENT: f32 = 0.7;
+
+// "Up" for a body: against its local gravity, or the world's up without one.
+fn up_for(gravity: Option<&LocalGravity>) -> Vec2 {
+    gravity
+        .and_then(|gravity| (-gravity.0).try_normalize())
+        .unwrap_or(Vec2::Y)
+}
+
+// Runs before the physics step, while falling characters still have their speed.
+fn track_fall_speed(
+    mut attackers: Query<(&mut StompAttack, &LinearVelocity, Option<&LocalGravity>)>,
+) {
+    for (mut attack, velocity, gravity) in &mut attackers {
+        attack.fall_speed = attack.fall_speed.max(-velocity.0.dot(up_for(gravity)));
+    }
+}
+
+// Starts tracking the fall over once `head_stomps` has seen the latest collisions.
+fn reset_fall_speed(mut attackers: Query<&mut StompAttack>) {
+    for mut attack in &mut attackers {
+        attack.fall_speed = 0.0;
+    }
+}
+
+// Damages characters that others have landed on from above, and bounces the attackers.
+fn head_stomps(
+    mut collision_started: EventReader<CollisionStarted>,
+    attackers: Query<(&StompAttack, &Position, Option<&LocalGravity>)>,
+    positions: Query<&Position, With<CharacterController>>,
+    mut bodies: ParamSet<(
+        Query<HitTarget, Without<Invulnerable>>,
+        Query<&mut LinearVelocity>,
+    )>,
+    mut damage: DamageWriter,
+) {
+    for CollisionStarted(a, b) in collision_started.read() {
+        for (attacker, victim) in [(*a, *b), (*b, *a)] {
+            let (Ok((attack, attacker_position, gravity)), Ok(victim_position)) =
+                (attackers.get(attacker), positions.get(victim))
+            else {
+                continue;
+            };
+            let up = up_for(gravity);
+            let from_victim = (attacker_position.0 - victim_position.0).normalize_or_zero();
+            if from_victim.dot(up) < HEAD_STOMP_ALIGNMENT || attack.fall_speed < attack.min_speed {
+                continue;
+            }
+            if let Ok(mut target) = bodies.p0().get_mut(victim) {
+                damage.hit(&mut target, attack.damage, -up, 0.0, Some(attacker));
+            }
+            if let Ok(mut velocity) = bodies.p1().get_mut(attacker) {
+                // Replace whatever is left of the fall with the bounce
+                let along_up = velocity.0.dot(up);
+                velocity.0 += up * (attack.bounce - along_up);
+            }
+        }
+    }
+}
+
+// How long a dead character's body tumbles around before it disappears, in seconds.
+const RAGDOLL_SECS: f32 = 3.0;
+// How hard the body is flung in a random direction on death.
+const RAGDOLL_IMPULSE: f32 = 250.0;
+// The most the body spins on death, in radians per second.
+const RAGDOLL_SPIN: f32 = 10.0;
+
+// Leaves a copy of every dead character behind that tumbles freely, without the rotation
+// lock characters have. The character itself is despawned as usual, so respawning builds
+// a fresh one with the lock back in place.
+fn spawn_ragdolls(
+    mut commands: Commands,
+    mut death_event_reader: EventReader<DeathEvent>,
+    mut rng: ResMut<GameRng>,
+    match_config: Res<MatchConfig>,
+    characters: Query<
+        (
+            &Transform,
+            &LinearVelocity,
+            &Collider,
+            Option<&Mesh2d>,
+            Option<&MeshMaterial2d<ColorMaterial>>,
+        ),
+        With<CharacterController>,
+    >,
+) {
+    if !match_config.ragdolls {
+        death_event_reader.clear();
+        return;
+    }
+    for death in death_event_reader.read() {
+        let Ok((transform, velocity, collider, mesh, material)) = characters.get(death.victim)
+        else {
+            continue;
+        };
+        let mut ragdoll = commands.spawn((
+            *transform,
+            RigidBody::Dynamic,
+            collider.clone(),
+            layers::debris(),
+            LinearVelocity(velocity.0 + rng.direction() * RAGDOLL_IMPULSE),
+            AngularVelocity(rng.float(-RAGDOLL_SPIN..RAGDOLL_SPIN)),
+            Debris {
+                timer: Timer::from_seconds(RAGDOLL_SECS, TimerMode::Once),
+            },
+        ));
+        if let (Some(mesh), Some(material)) = (mesh, material) {
+            ragdoll.insert((mesh.clone(), material.clone()));
+        }
+    }
+}
+
+fn despawn_dead(
+    mut commands: Commands,
+    mut death_event_reader: EventReader<DeathEvent>,
+    mut assignments: ResMut<PlayerAssignments>,
+    mut spectating: ResMut<Spectating>,
+    characters: Query<(), With<CharacterController>>,
+) {
+    for death in death_event_reader.read() {
+        if characters.contains(death.victim) {
+            kill_character(&mut commands, &mut assignments, &mut spectating, death.victim);
+        }
+    }
+}
+
+// Sets off barrels that have been destroyed. Barrels caught in the blast go off in
+// turn on the next frame, so chains ripple outwards.
+fn detonate_barrels(
+    mut death_event_reader: EventReader<DeathEvent>,
+    mut explosion_writer: EventWriter<Explosion>,
+    barrels: Query<(&ExplosiveBarrel, &Transform)>,
+) {
+    for death in death_event_reader.read() {
+        if let Ok((barrel, transform)) = barrels.get(death.victim) {
+            explosion_writer.send(Explosion {
+                center: transform.translation.truncate(),
+                radius: barrel.radius,
+                damage: barrel.damage,
+                source: death.killer,
+            });
+        }
+    }
+}
+
+fn destroy_destructibles(
+    mut commands: Commands,
+    mut death_event_reader: EventReader<DeathEvent>,
+    mut rng: ResMut<GameRng>,
+    destructibles: Query<(&Transform, Option<&Sprite>), With<Destructible>>,
+) {
+    for death in death_event_reader.read() {
+        let Ok((transform, sprite)) = destructibles.get(death.victim) else {
+            continue;
+        };
+        let color = sprite.map_or(Color::WHITE, |sprite| sprite.color);
+        for _ in 0..DEBRIS_PIECES {
+            let direction = rng.direction();
+            commands.spawn((
+                Sprite {
+                    color,
+                    custom_size: Some(Vec2::new(6.0, 6.0)),
+                    ..default()
+                },
+                Transform::from_translation(transform.translation),
+                RigidBody::Dynamic,
+                Collider::rectangle(6.0, 6.0),
+                layers::debris(),
+                LinearVelocity(direction * rng.float(100.0..300.0)),
+                Debris {
+                    timer: Timer::from_seconds(1.5, TimerMode::Once),
+                },
+            ));
+        }
+        commands.entity(death.victim).despawn_recursive();
+    }
+}
+
+fn expire_debris(mut commands: Commands, time: Res<Time>, mut debris: Query<(Entity, &mut Debris)>) {
+    for (entity, mut debris) in &mut debris {
+        if debris.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn fade_explosions(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut flashes: Query<(Entity, &mut ExplosionFlash, &MeshMaterial2d<ColorMaterial>)>,
+) {
+    for (entity, mut flash, material) in &mut flashes {
+        if flash.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        } else if let Some(material) = materials.get_mut(&material.0) {
+            material.color.set_alpha(0.6 * flash.timer.fraction_remaining());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn fall_speed_is_kept_across_physics_steps() {
+        let mut world = World::new();
+        let attacker = world
+            .spawn((StompAttack::default(), LinearVelocity(Vec2::new(0.0, -500.0))))
+            .id();
+        world.run_system_once(track_fall_speed).unwrap();
+        // Landing on the next step stops the fall before the frame sees the collision
+        world.get_mut::<LinearVelocity>(attacker).unwrap().0 = Vec2::ZERO;
+        world.run_system_once(track_fall_speed).unwrap();
+        assert_eq!(world.get::<StompAttack>(attacker).unwrap().fall_speed, 500.0);
+
+        world.run_system_once(reset_fall_speed).unwrap();
+        world.run_system_once(track_fall_speed).unwrap();
+        assert_eq!(world.get::<StompAttack>(attacker).unwrap().fall_speed, 0.0);
+    }
+
+    #[test]
+    fn shield_soaks_up_hits_smaller_than_it() {
+        let mut shield = Shield::new(50.0);
+        assert_eq!(shield.absorb(20.0), 0.0);
+        assert_eq!(shield.current, 30.0);
+        assert!(!shield.is_depleted());
+    }
+
+    #[test]
+    fn shield_soaks_up_a_hit_exactly_its_size() {
+        let mut shield = Shield::new(50.0);
+        assert_eq!(shield.absorb(50.0), 0.0);
+        assert_eq!(shield.current, 0.0);
+        assert!(shield.is_depleted());
+    }
+
+    #[test]
+    fn shield_passes_on_what_it_cant_soak_up() {
+        let mut shield = Shield::new(50.0);
+        assert_eq!(shield.absorb(80.0), 30.0);
+        assert_eq!(shield.current, 0.0);
+        // Once it's gone everything goes through
+        assert_eq!(shield.absorb(10.0), 10.0);
+    }
+
+    // Hits a character with 100 health, and optionally a 50 point shield, for `amount`.
+    // Returns the health and shield it's left with, and the damage events sent as the
+    // health lost and the damage absorbed.
+    fn hit_character(amount: f32, shield: bool) -> (f32, Option<f32>, Vec<(f32, f32)>) {
+        let mut world = World::new();
+        world.insert_resource(MatchConfig::default());
+        world.init_resource::<SandboxSettings>();
+        world.init_resource::<Events<DamageEvent>>();
+        world.init_resource::<Events<DeathEvent>>();
+        let target = world.spawn((Damageable::new(100.0), Transform::default())).id();
+        if shield {
+            world.entity_mut(target).insert(Shield::new(50.0));
+        }
+        world
+            .run_system_once(move |mut damage: DamageWriter, mut targets: Query<HitTarget>| {
+                let mut hit = targets.get_mut(target).unwrap();
+                damage.hit(&mut hit, amount, Vec2::X, 0.0, None);
+            })
+            .unwrap();
+        let health = world.get::<Damageable>(target).unwrap().health;
+        let shield = world.get::<Shield>(target).map(|shield| shield.current);
+        let events = world.resource::<Events<DamageEvent>>();
+        let damage = events
+            .iter_current_update_events()
+            .map(|event| (event.amount, event.absorbed))
+            .collect();
+        (health, shield, damage)
+    }
+
+    #[test]
+    fn shielded_hits_only_reach_health_past_the_shield() {
+        assert_eq!(hit_character(20.0, true), (100.0, Some(30.0), vec![(0.0, 20.0)]));
+        assert_eq!(hit_character(50.0, true), (100.0, Some(0.0), vec![(0.0, 50.0)]));
+        assert_eq!(hit_character(80.0, true), (70.0, Some(0.0), vec![(30.0, 50.0)]));
+        assert_eq!(hit_character(80.0, false), (20.0, None, vec![(80.0, 0.0)]));
+    }
+
+    // Bashes a player standing right in front of the attacker and returns its velocity.
+    fn bash_player(god_mode: bool, mode: GameMode) -> Vec2 {
+        let mut world = World::new();
+        world.insert_resource(MatchConfig {
+            mode,
+            ..default()
+        });
+        world.insert_resource(SandboxSettings {
+            god_mode,
+            ..default()
+        });
+        world.init_resource::<Events<DamageEvent>>();
+        world.init_resource::<Events<DeathEvent>>();
+        world.init_resource::<Events<GunBash>>();
+        let attacker = world.spawn_empty().id();
+        let target = world
+            .spawn((
+                Damageable::new(100.0),
+                Transform::from_xyz(20.0, 0.0, 0.0),
+                LinearVelocity::ZERO,
+                CharacterController,
+                PlayerId(0),
+            ))
+            .id();
+        world.send_event(GunBash {
+            attacker,
+            origin: Vec2::ZERO,
+            direction: Vec2::X,
+        });
+        world.run_system_once(land_gun_bashes).unwrap();
+        world.get::<LinearVelocity>(target).unwrap().0
+    }
+
+    #[test]
+    fn gun_bashes_push_through_the_damage_writer() {
+        assert_eq!(bash_player(false, GameMode::default()), Vec2::X * GUN_BASH_KNOCKBACK);
+        assert_eq!(bash_player(true, GameMode::default()), Vec2::ZERO);
+        // Sumo doesn't add its own push on top of the bash's
+        let sumo = GUN_BASH_KNOCKBACK.max(GUN_BASH_DAMAGE * SUMO_KNOCKBACK);
+        assert_eq!(bash_player(false, GameMode::Sumo), Vec2::X * sumo);
+    }
+
+    // Blows up a 10 point explosion at the origin next to a character `distance` away
+    // and returns its velocity.
+    fn blast_at(distance: f32) -> Vec2 {
+        let mut world = World::new();
+        world.insert_resource(MatchConfig::default());
+        world.init_resource::<SandboxSettings>();
+        world.init_resource::<Events<DamageEvent>>();
+        world.init_resource::<Events<DeathEvent>>();
+        let target = world
+            .spawn((
+                Damageable::new(100.0),
+                Transform::from_xyz(distance, 0.0, 0.0),
+                LinearVelocity::ZERO,
+            ))
+            .id();
+        world
+            .run_system_once(|mut damage: DamageWriter, mut targets: Query<HitTarget>| {
+                explode(Vec2::ZERO, 100.0, 10.0, None, &mut targets, &mut damage);
+            })
+            .unwrap();
+        world.get::<LinearVelocity>(target).unwrap().0
+    }
+
+    #[test]
+    fn explosions_push_harder_up_close() {
+        let near = blast_at(10.0);
+        let far = blast_at(80.0);
+        assert!(near.x > far.x && far.x > 0.0);
+        assert_eq!(near.y, 0.0);
+        assert_eq!(blast_at(120.0), Vec2::ZERO);
+    }
+
+    // Blows up a character's own 10 point explosion `offset` away from it and returns
+    // its velocity.
+    fn own_blast_at(offset: Vec2) -> Vec2 {
+        let mut world = World::new();
+        world.insert_resource(MatchConfig {
+            self_damage: true,
+            ..default()
+        });
+        world.init_resource::<SandboxSettings>();
+        world.init_resource::<Events<DamageEvent>>();
+        world.init_resource::<Events<DeathEvent>>();
+        let target = world
+            .spawn((
+                Damageable::new(100.0),
+                Transform::default(),
+                LinearVelocity::ZERO,
+                LocalGravity(Vec2::X * -100.0),
+            ))
+            .id();
+        world
+            .run_system_once(move |mut damage: DamageWriter, mut targets: Query<HitTarget>| {
+                let source = Some(target);
+                explode(-offset, 100.0, 10.0, source, &mut targets, &mut damage);
+            })
+            .unwrap();
+        world.get::<LinearVelocity>(target).unwrap().0
+    }
+
+    #[test]
+    fn own_blasts_push_less_from_further_away() {
+        let near = own_blast_at(Vec2::Y * 10.0);
+        let far = own_blast_at(Vec2::Y * 80.0);
+        assert!(near.y > far.y && far.y > 0.0);
+    }
+
+    #[test]
+    fn own_blasts_right_underfoot_push_up() {
+        // Gravity pulls towards -x, so up is +x
+        let velocity = own_blast_at(Vec2::ZERO);
+        assert!(velocity.x > 0.0);
+        assert_eq!(velocity.y, 0.0);
+    }
+}
@@ -0,0 +1,129 @@
+use avian2d::prelude::*;
+use bevy::prelude::*;
+
+use crate::player::CharacterController;
+use crate::weapons::{Heat, Projectile, Weapon};
+
+pub struct DebugPlugin;
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(PhysicsDebugPlugin::default())
+            .insert_resource(DebugRender(false))
+            .insert_resource(SandboxSettings::default())
+            .insert_resource(Interpolation(true))
+            .add_systems(Update, (toggle_debug_render, apply_debug_render).chain())
+            .add_systems(Update, (toggle_sandbox, apply_sandbox).chain())
+            .add_systems(Update, (toggle_interpolation, apply_interpolation).chain());
+    }
+}
+
+// Whether colliders, contacts and other physics internals are drawn on top of the game.
+#[derive(Resource)]
+pub struct DebugRender(pub bool);
+
+// Cheats for tuning weapons in peace. F7 toggles infinite ammo, F8 removes weapon
+// cooldowns and overheating, and F9 makes players immune to hits.
+#[derive(Resource, Default)]
+pub struct SandboxSettings {
+    pub infinite_ammo: bool,
+    pub no_cooldown: bool,
+    pub god_mode: bool,
+}
+
+// Whether characters and projectiles are drawn between their last two physics positions,
+// so that they move smoothly when the frame rate is higher than the physics tick rate.
+// Backquote toggles it to compare.
+#[derive(Resource)]
+pub struct Interpolation(pub bool);
+
+fn toggle_debug_render(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut debug_render: ResMut<DebugRender>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F4) {
+        debug_render.0 = !debug_render.0;
+    }
+}
+
+fn apply_debug_render(
+    debug_render: Res<DebugRender>,
+    mut config_store: ResMut<GizmoConfigStore>,
+) {
+    if !debug_render.is_changed() {
+        return;
+    }
+    config_store.config_mut::<PhysicsGizmos>().0.enabled = debug_render.0;
+}
+
+fn toggle_sandbox(keyboard_input: Res<ButtonInput<KeyCode>>, mut sandbox: ResMut<SandboxSettings>) {
+    let sandbox = &mut *sandbox;
+    let toggles = [
+        (KeyCode::F7, &mut sandbox.infinite_ammo, "Infinite ammo"),
+        (KeyCode::F8, &mut sandbox.no_cooldown, "No cooldown"),
+        (KeyCode::F9, &mut sandbox.god_mode, "God mode"),
+    ];
+    for (key, setting, name) in toggles {
+        if keyboard_input.just_pressed(key) {
+            *setting = !*setting;
+            info!("{name}: {}", if *setting { "on" } else { "off" });
+        }
+    }
+}
+
+fn toggle_interpolation(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut interpolation: ResMut<Interpolation>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Backquote) {
+        interpolation.0 = !interpolation.0;
+        info!("Interpolation: {}", if interpolation.0 { "on" } else { "off" });
+    }
+}
+
+// Hands the smoothing itself to Avian's transform interpolation.
+fn apply_interpolation(
+    mut commands: Commands,
+    interpolation: Res<Interpolation>,
+    bodies: Query<
+        (Entity, Has<TransformInterpolation>, Ref<RigidBody>),
+        Or<(With<CharacterController>, With<Projectile>)>,
+    >,
+) {
+    for (entity, interpolated, body) in &bodies {
+        // Only newly spawned bodies need looking at, unless the setting just changed
+        if !interpolation.is_changed() && !body.is_added() {
+            continue;
+        }
+        if interpolation.0 && !interpolated {
+            commands.entity(entity).insert(TransformInterpolation);
+        } else if !interpolation.0 && interpolated {
+            commands.entity(entity).remove::<TransformInterpolation>();
+        }
+    }
+}
+
+// Keeps every weapon loaded and ready to fire, so that the firing code itself doesn't
+// need to know about the sandbox.
+fn apply_sandbox(
+    sandbox: Res<SandboxSettings>,
+    mut weapons: Query<(&mut Weapon, Option<&mut Heat>)>,
+) {
+    if !sandbox.infinite_ammo && !sandbox.no_cooldown {
+        return;
+    }
+    for (mut weapon, heat) in &mut weapons {
+        if sandbox.infinite_ammo {
+            let full = Weapon::new(weapon.kind).ammo;
+            weapon.ammo = full;
+        }
+        if sandbox.no_cooldown {
+            let duration = weapon.cooldown.duration();
+            weapon.cooldown.tick(duration);
+            if let Some(mut heat) = heat {
+                heat.current = 0.0;
+                heat.overheated = false;
+            }
+        }
+    }
+}
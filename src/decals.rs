@@ -0,0 +1,130 @@
+use avian2d::prelude::*;
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+use crate::gravity::Planet;
+use crate::weapons::Projectile;
+
+pub struct DecalPlugin;
+
+impl Plugin for DecalPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(DecalSettings::default())
+            .insert_resource(DecalPool::default())
+            .add_systems(Update, (spawn_impact_decals, fade_decals).chain());
+    }
+}
+
+// Limits on the scorch marks projectiles leave on planets.
+#[derive(Resource)]
+pub struct DecalSettings {
+    // How many marks can exist at once. Past this, the oldest mark is moved to the new impact.
+    pub max_decals: usize,
+    // How long a mark takes to fade away, in seconds.
+    pub fade_secs: f32,
+}
+
+impl Default for DecalSettings {
+    fn default() -> Self {
+        Self {
+            max_decals: 64,
+            fade_secs: 8.0,
+        }
+    }
+}
+
+// Every mark spawned so far, oldest first.
+#[derive(Resource, Default)]
+pub struct DecalPool(pub VecDeque<Entity>);
+
+// A scorch mark, parented to the planet it's on.
+#[derive(Component)]
+pub struct Decal {
+    pub timer: Timer,
+}
+
+const DECAL_SIZE: Vec2 = Vec2::new(14.0, 4.0);
+const DECAL_ALPHA: f32 = 0.7;
+
+fn spawn_impact_decals(
+    mut commands: Commands,
+    settings: Res<DecalSettings>,
+    mut pool: ResMut<DecalPool>,
+    collisions: Res<Collisions>,
+    mut collision_event_reader: EventReader<CollisionStarted>,
+    projectiles: Query<(), With<Projectile>>,
+    planets: Query<(), With<Planet>>,
+) {
+    for CollisionStarted(entity1, entity2) in collision_event_reader.read() {
+        let Some(contacts) = collisions.get(*entity1, *entity2) else {
+            continue;
+        };
+        for (planet, projectile) in [(*entity1, *entity2), (*entity2, *entity1)] {
+            if !planets.contains(planet) || !projectiles.contains(projectile) {
+                continue;
+            }
+            // Contact points are in the planet's local space, so the mark can be
+            // parented to the planet as is and turn along with it
+            let Some(point) = contacts
+                .manifolds
+                .iter()
+                .flat_map(|manifold| &manifold.contacts)
+                .map(|contact| {
+                    if contacts.entity1 == planet {
+                        contact.point1
+                    } else {
+                        contact.point2
+                    }
+                })
+                .next()
+            else {
+                continue;
+            };
+            let transform = Transform {
+                translation: point.extend(0.5),
+                // Lay the mark flat along the surface
+                rotation: Quat::from_rotation_z(point.to_angle() - std::f32::consts::FRAC_PI_2),
+                ..default()
+            };
+            let decal = Decal {
+                timer: Timer::from_seconds(settings.fade_secs, TimerMode::Once),
+            };
+
+            let recycled = if pool.0.len() >= settings.max_decals {
+                pool.0.pop_front()
+            } else {
+                None
+            };
+            let entity = match recycled {
+                Some(entity) => {
+                    commands.entity(entity).try_insert((transform, decal));
+                    entity
+                }
+                None => commands
+                    .spawn((
+                        Sprite {
+                            color: Color::srgba(0.1, 0.08, 0.05, DECAL_ALPHA),
+                            custom_size: Some(DECAL_SIZE),
+                            ..default()
+                        },
+                        transform,
+                        decal,
+                    ))
+                    .id(),
+            };
+            commands.entity(planet).add_child(entity);
+            pool.0.push_back(entity);
+        }
+    }
+}
+
+// Fades marks out over their lifetime. Faded marks stay around, invisible, until they're recycled.
+fn fade_decals(time: Res<Time>, mut decals: Query<(&mut Decal, &mut Sprite)>) {
+    for (mut decal, mut sprite) in &mut decals {
+        if decal.timer.finished() {
+            continue;
+        }
+        decal.timer.tick(time.delta());
+        sprite.color.set_alpha(DECAL_ALPHA * decal.timer.fraction_remaining());
+    }
+}
@@ -1,15 +1,212 @@
 use avian2d::{math::*, prelude::*};
-use bevy::{
-  prelude::*,
-  render::{render_asset::RenderAssetUsages, render_resource::PrimitiveTopology},
-};
+use bevy::prelude::*;
 
 use crate::player::{
+  CharacterController,
   CharacterControllerBundle,
+  Glide,
+  GroundSnap,
+  InputDevice,
+  JumpGravity,
+  MovementBundle,
+  MovementMode,
+  MovingPlatformRider,
   PlayerAssignments,
+  PlayerId,
+  Stomp,
+  TerrainModifier,
+  TurnBoost,
+};
+
+use crate::weapons::{
+  Gun, GunColor, GunVisual, Heat, Loadout, PlacedMines, Projectile, SelfImpulse, Spread, Weapon,
+  WeaponKind,
 };
+use crate::combat::{
+  DamageWriter,
+  Damageable,
+  Destructible,
+  ExplosiveBarrel,
+  Invulnerable,
+  LastHitBy,
+  StompAttack,
+};
+use crate::grapple::Grapple;
+use crate::gravity::{Planet, WorldGravity};
+use crate::hazards::{ForceZone, GravityWell};
+use crate::bindings::InputBindings;
+use crate::camera::Spectating;
+use crate::hud::Announcement;
+use crate::layers;
+use crate::lobby::{PlayerClasses, PlayerColors};
+use crate::powerups::{spawn_powerup, PowerupKind};
+use crate::training::spawn_dummy;
+use crate::trajectory::TrajectoryPreview;
+
+// The playable area. Anything that leaves it is considered lost to the void.
+#[derive(Resource)]
+pub struct WorldBounds {
+  pub min: Vec2,
+  pub max: Vec2,
+}
+
+impl WorldBounds {
+  pub fn contains(&self, point: Vec2) -> bool {
+    point.cmpge(self.min).all() && point.cmple(self.max).all()
+  }
+}
+
+impl Default for WorldBounds {
+  fn default() -> Self {
+    Self {
+      min: Vec2::new(-2500.0, -2500.0),
+      max: Vec2::new(2500.0, 2000.0),
+    }
+  }
+}
+
+// Rules for the current match.
+#[derive(Resource)]
+pub struct MatchConfig {
+  // How long characters are protected from damage after (re)spawning, in seconds.
+  pub invulnerability_secs: f32,
+  // Length of a round before sudden death kicks in, in seconds.
+  pub round_secs: f32,
+  // How many players can join the game at once. Bots don't count.
+  pub max_players: usize,
+  // How every character moves. F6 switches between platforming and zero-g space.
+  pub movement_mode: MovementMode,
+  // What players are trying to do to each other. Picked in the lobby.
+  pub mode: GameMode,
+  // Whether explosions hit the character who set them off, allowing rocket jumps.
+  pub self_damage: bool,
+  // Whether dead characters leave a tumbling body behind for a moment.
+  pub ragdolls: bool,
+  // Whether pulling the trigger of an empty gun swings it at whoever is in front.
+  pub gun_bash: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GameMode {
+  // Shoot each other down.
+  #[default]
+  Deathmatch,
+  // Weapons only knock characters back. Points come from pushing others out of the world.
+  Sumo,
+}
+
+impl GameMode {
+  pub fn name(&self) -> &'static str {
+    match self {
+      GameMode::Deathmatch => "Deathmatch",
+      GameMode::Sumo => "Sumo",
+    }
+  }
 
-use crate::weapons::{ Gun, Projectile };
+  pub fn next(&self) -> Self {
+    match self {
+      GameMode::Deathmatch => GameMode::Sumo,
+      GameMode::Sumo => GameMode::Deathmatch,
+    }
+  }
+}
+
+impl Default for MatchConfig {
+  fn default() -> Self {
+    Self {
+      invulnerability_secs: 2.0,
+      round_secs: 180.0,
+      max_players: 4,
+      movement_mode: MovementMode::Platformer,
+      mode: GameMode::Deathmatch,
+      self_damage: false,
+      ragdolls: true,
+      gun_bash: true,
+    }
+  }
+}
+
+// Where characters can enter the world.
+#[derive(Resource)]
+pub struct SpawnPoints(pub Vec<Vec2>);
+
+impl SpawnPoints {
+  // Picks the spawn point furthest away from any `occupied` position, so that
+  // characters joining at the same time don't end up on top of each other.
+  // Ties go to the earliest point in the list.
+  pub fn pick(&self, occupied: &[Vec2]) -> Vec2 {
+    self.0
+      .iter()
+      .copied()
+      .map(|point| {
+          let clearance = occupied
+              .iter()
+              .map(|position| position.distance_squared(point))
+              .fold(f32::INFINITY, f32::min);
+          (point, clearance)
+      })
+      .fold(None, |best: Option<(Vec2, f32)>, candidate| match best {
+          Some(best) if best.1 >= candidate.1 => Some(best),
+          _ => Some(candidate),
+      })
+      .map_or(Vec2::ZERO, |(point, _)| point)
+  }
+}
+
+// Plenty of segments so the planet's outline looks smooth at its size. The collider is
+// a true circle, and with this many segments the flat edges of the mesh stay within a
+// pixel of it.
+fn planet_mesh(radius: f32) -> Mesh {
+  Circle::new(radius).mesh().resolution(256).build()
+}
+
+// Spawns the crates and barrels, which can be destroyed during a match and are put back
+// when it restarts.
+pub fn spawn_props(commands: &mut Commands) {
+  // Destructible crates to take cover behind
+  for position in [
+      Vec2::new(-200.0, -184.0),
+      Vec2::new(-200.0, -144.0),
+      Vec2::new(180.0, -183.0),
+  ] {
+      commands.spawn((
+          Sprite {
+              color: Color::srgb(0.55, 0.35, 0.15),
+              custom_size: Some(Vec2::new(40.0, 40.0)),
+              ..default()
+          },
+          Transform::from_translation(position.extend(0.0)),
+          RigidBody::Static,
+          Collider::rectangle(40.0, 40.0),
+          layers::item(),
+          Damageable::new(60.0),
+          Destructible,
+      ));
+  }
+
+  // A cluster of explosive barrels, close enough together to go off in a chain
+  for position in [
+      Vec2::new(-455.0, -201.0),
+      Vec2::new(-420.0, -198.0),
+      Vec2::new(-385.0, -195.0),
+      Vec2::new(-420.0, -158.0),
+  ] {
+      commands.spawn((
+          Sprite {
+              color: Color::srgb(0.8, 0.15, 0.1),
+              custom_size: Some(Vec2::new(30.0, 40.0)),
+              ..default()
+          },
+          Transform::from_translation(position.extend(0.0)),
+          RigidBody::Static,
+          Collider::rectangle(30.0, 40.0),
+          layers::item(),
+          Damageable::new(30.0),
+          Destructible,
+          ExplosiveBarrel::default(),
+      ));
+  }
+}
 
 pub fn setup(
   mut commands: Commands,
@@ -27,55 +224,16 @@ pub fn setup(
       RigidBody::Dynamic,
       Mass(5.0),
       Collider::rectangle(30.0, 30.0),
+      layers::item(),
       //Friction::new(0.4).with_dynamic_coefficient(0.6).with_static_coefficient(0.6)
   ));
 
   // Planet surface (large circle)
   let planet_radius = 5000.0; // Large radius so only part is visible
 
-  // Create a circle mesh with many vertices to make it smooth
-  let segments = 256;
-  let mut circle_mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default());
-
-  // Vertices for the circle
-  let mut positions = Vec::with_capacity(segments * 3);
-
-  // Create a filled circle using triangles from center
-  for i in 0..segments {
-    let angle1 = 2.0 * std::f32::consts::PI * (i as f32) / (segments as f32);
-    let angle2 = 2.0 * std::f32::consts::PI * ((i + 1) as f32) / (segments as f32);
-
-    // Center point
-    positions.push([0.0, 0.0, 0.0]);
-    // First outer point
-    positions.push([
-        planet_radius * angle1.cos(), 
-        planet_radius * angle1.sin(), 
-        0.0
-    ]);
-    // Second outer point
-    positions.push([
-        planet_radius * angle2.cos(), 
-        planet_radius * angle2.sin(), 
-        0.0
-    ]);
-  }
-
-  circle_mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
-
-  // Create a circle collider (assuming this exists in your physics system)
+  let circle_mesh = planet_mesh(planet_radius);
   let circle_collider = Collider::circle(planet_radius);
-  let mut polygon_vertices = Vec::with_capacity(segments);
-  for i in 0..segments {
-    let angle = 2.0 * std::f32::consts::PI * (i as f32) / (segments as f32);
-    polygon_vertices.push(Vec2::new(
-      planet_radius * angle.cos(),
-      planet_radius * angle.sin()
-    ));
-  }
 
-  let polygon_collider = Collider::polyline(polygon_vertices, None);
-  
   commands.spawn((
       Mesh2d(meshes.add(circle_mesh)),
       MeshMaterial2d(materials.add(Color::srgb(0.5, 0.8, 0.5))),
@@ -83,77 +241,654 @@ pub fn setup(
       Transform::from_xyz(0.0, -5200.0, 0.0),
       RigidBody::Kinematic,
       circle_collider,
+      layers::world(),
       AngularVelocity(0.01),
+      Planet {
+          radius: planet_radius,
+          surface_gravity: 1000.0,
+      },
       //Friction::new(0.4).with_dynamic_coefficient(0.6).with_static_coefficient(0.6)
   ));
 
+  // A small moon up in the sky, strong enough to walk around on
+  let moon_radius = 200.0;
+  commands.spawn((
+      Mesh2d(meshes.add(Circle::new(moon_radius).mesh().resolution(64))),
+      MeshMaterial2d(materials.add(Color::srgb(0.6, 0.6, 0.65))),
+      Transform::from_xyz(-1800.0, 1300.0, 0.0),
+      RigidBody::Static,
+      Collider::circle(moon_radius),
+      layers::world(),
+      Planet {
+          radius: moon_radius,
+          surface_gravity: 2000.0,
+      },
+  ));
+
+  spawn_props(&mut commands);
+
+  // A slippery ice ledge and a sticky mud ledge
+  for (position, color, modifier) in [
+      (
+          Vec2::new(-380.0, 20.0),
+          Color::srgb(0.75, 0.9, 1.0),
+          TerrainModifier { damping_mul: 0.1, accel_mul: 0.4 },
+      ),
+      (
+          Vec2::new(120.0, 60.0),
+          Color::srgb(0.35, 0.25, 0.15),
+          TerrainModifier { damping_mul: 3.0, accel_mul: 0.5 },
+      ),
+  ] {
+      commands.spawn((
+          Sprite {
+              color,
+              custom_size: Some(Vec2::new(200.0, 20.0)),
+              ..default()
+          },
+          Transform::from_translation(position.extend(0.0)),
+          RigidBody::Static,
+          Collider::rectangle(200.0, 20.0),
+          layers::world(),
+          modifier,
+      ));
+  }
+
+  // A training dummy for trying out weapons
+  spawn_dummy(&mut commands, &mut meshes, &mut materials, Vec2::new(480.0, -200.0));
+
+  // A gravity well hanging above the surface, bending the path of anything that passes by
+  let well_center = Vec2::new(350.0, 150.0);
+  let well_radius = 250.0;
+  commands.spawn((
+      Mesh2d(meshes.add(Circle::new(well_radius))),
+      MeshMaterial2d(materials.add(Color::srgba(0.5, 0.2, 0.8, 0.15))),
+      Transform::from_translation(well_center.extend(-1.0)),
+      GravityWell {
+          center: well_center,
+          strength: 60000.0,
+          radius: well_radius,
+      },
+  ));
+
+  // An updraft strong enough to lift characters off the ground
+  let updraft = Rect::new(-700.0, -260.0, -560.0, 300.0);
+  commands.spawn((
+      Sprite {
+          color: Color::srgba(0.6, 0.8, 1.0, 0.1),
+          custom_size: Some(updraft.size()),
+          ..default()
+      },
+      Transform::from_translation(updraft.center().extend(-1.0)),
+      Collider::rectangle(updraft.width(), updraft.height()),
+      ForceZone {
+          force: Vec2::new(0.0, 2500.0),
+      },
+  ));
+
+  // A bounciness powerup at the top of the updraft
+  spawn_powerup(
+      &mut commands,
+      Vec2::new(-630.0, 340.0),
+      PowerupKind::Bouncy {
+          restitution: 0.9,
+          secs: 10.0,
+      },
+  );
+
+  // A magnet powerup on the ground to the right, pulling in the other pickups
+  spawn_powerup(
+      &mut commands,
+      Vec2::new(200.0, -200.0),
+      PowerupKind::Magnet {
+          radius: 500.0,
+          strength: 400.0,
+          secs: 12.0,
+      },
+  );
+
+  // A shield powerup on the mud ledge
+  spawn_powerup(
+      &mut commands,
+      Vec2::new(120.0, 90.0),
+      PowerupKind::Shield { amount: 50.0 },
+  );
+
+  commands.insert_resource(SpawnPoints(vec![
+      Vec2::new(-300.0, -100.0),
+      Vec2::new(300.0, -100.0),
+      Vec2::new(-100.0, -100.0),
+      Vec2::new(100.0, -100.0),
+  ]));
+
   // Camera
   commands.spawn(Camera2d);
 }
 
+// The capsule a character is shaped like.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct CharacterSize {
+  pub radius: f32,
+  // Length of the straight middle part, not counting the rounded ends.
+  pub length: f32,
+}
+
+impl Default for CharacterSize {
+  fn default() -> Self {
+    Self {
+      radius: 12.5,
+      length: 20.0,
+    }
+  }
+}
+
+impl CharacterSize {
+  pub fn collider(&self) -> Collider {
+    Collider::capsule(self.radius, self.length)
+  }
+
+  pub fn mesh(&self) -> Capsule2d {
+    Capsule2d::new(self.radius, self.length)
+  }
+
+  // How far along the aim projectiles spawn, just clear of the capsule in any direction.
+  pub fn muzzle_offset(&self) -> f32 {
+    self.radius + self.length / 2.0 + 2.5
+  }
+}
+
+// Everything that sets one character apart from another when spawning it.
+#[derive(Clone)]
+pub struct SpawnConfig {
+  pub position: Vec2,
+  pub color: Color,
+  pub movement: MovementBundle,
+  pub weapon: WeaponKind,
+  // A second gun to dual wield, fired with its own trigger.
+  pub secondary_weapon: Option<WeaponKind>,
+  pub friction: Friction,
+  // How long the character is protected from damage after spawning, in seconds.
+  pub invulnerability_secs: f32,
+  // Whether and how the character can glide.
+  pub glide: Option<Glide>,
+  // Whether and how strongly the character is kept on the ground over slopes.
+  pub ground_snap: Option<GroundSnap>,
+  // Whether and how hard the character brakes when turning around. Off unless the
+  // class asks for it.
+  pub turn_boost: Option<TurnBoost>,
+  pub size: CharacterSize,
+  pub health: f32,
+  // How much the character bounces off surfaces, from 0 (not at all) to 1.
+  pub restitution: f32,
+}
+
+impl Default for SpawnConfig {
+  fn default() -> Self {
+    Self {
+      position: Vec2::ZERO,
+      color: Color::srgb(0.9, 0.1, 0.1),
+      movement: MovementBundle::default()
+          .acceleration(1250.0)
+          .damping(0.92)
+          .jump_impulse(800.0)
+          .max_slope_deg(30.0)
+          .terminal_velocity(1500.0)
+          // Up and to the right until the player aims somewhere else
+          .aiming(Quat::from_rotation_z(PI * 0.75))
+          .aim_smoothing(25.0)
+          .jump_gravity(JumpGravity {
+              rising_scale: 1.5,
+              falling_scale: 2.4,
+              apex_scale: 0.75,
+              ..JumpGravity::default()
+          }),
+      weapon: WeaponKind::Pistol,
+      secondary_weapon: None,
+      friction: Friction::new(0.8).with_dynamic_coefficient(0.8).with_static_coefficient(0.8),
+      invulnerability_secs: MatchConfig::default().invulnerability_secs,
+      glide: Some(Glide::default()),
+      ground_snap: Some(GroundSnap::default()),
+      turn_boost: None,
+      size: CharacterSize::default(),
+      health: 100.0,
+      restitution: 0.0,
+    }
+  }
+}
+
+// Presets players pick from before joining.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CharacterClass {
+  // Small, quick and fragile, with a rifle and a pistol on the side.
+  Light,
+  // The default character, with a pistol.
+  #[default]
+  Standard,
+  // Big, slow and tough, with grenades. Too heavy to glide.
+  Heavy,
+}
+
+impl CharacterClass {
+  pub const ALL: [CharacterClass; 3] = [
+    CharacterClass::Light,
+    CharacterClass::Standard,
+    CharacterClass::Heavy,
+  ];
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      CharacterClass::Light => "Light",
+      CharacterClass::Standard => "Standard",
+      CharacterClass::Heavy => "Heavy",
+    }
+  }
+
+  // The spawn config for a character of this class. Position, color and such are
+  // left at their defaults for the caller to fill in.
+  pub fn spawn_config(&self) -> SpawnConfig {
+    let base = SpawnConfig::default();
+    match self {
+      CharacterClass::Light => SpawnConfig {
+        movement: base.movement.clone().acceleration(1500.0).jump_impulse(900.0),
+        weapon: WeaponKind::Rifle,
+        secondary_weapon: Some(WeaponKind::Pistol),
+        // Quick on its feet, so it turns around on the spot
+        turn_boost: Some(TurnBoost::default()),
+        size: CharacterSize {
+          radius: 10.0,
+          length: 14.0,
+        },
+        health: 70.0,
+        ..base
+      },
+      CharacterClass::Standard => base,
+      CharacterClass::Heavy => SpawnConfig {
+        movement: base.movement.clone().acceleration(1000.0).jump_impulse(700.0),
+        weapon: WeaponKind::Grenade,
+        size: CharacterSize {
+          radius: 16.0,
+          length: 26.0,
+        },
+        health: 150.0,
+        glide: None,
+        ..base
+      },
+    }
+  }
+}
+
+// The color a character is drawn in. Its mesh and material are added by
+// `dress_characters`, so that spawning one doesn't need access to the assets.
+#[derive(Component)]
+pub struct CharacterColor(pub Color);
+
+/// Spawns a character with its gun, ready to be driven by `PlayerAction`s, and returns it.
+///
+/// This is the entry point for games embedding the controller. The character isn't
+/// controlled by anyone yet: send `PlayerAction`s for it yourself, or insert a
+/// `PlayerId` and register it in `PlayerAssignments` to hand it to a player.
+///
+/// ```no_run
+/// use bevy::prelude::*;
+/// use so_much_for_subtlety::game::{spawn_controllable_character, SpawnConfig};
+///
+/// fn spawn_player(mut commands: Commands) {
+///     let character = spawn_controllable_character(
+///         &mut commands,
+///         &SpawnConfig {
+///             position: Vec2::new(0.0, 100.0),
+///             color: Color::srgb(0.1, 0.4, 0.9),
+///             ..default()
+///         },
+///     );
+///     info!("Spawned {character}");
+/// }
+/// ```
+pub fn spawn_controllable_character(commands: &mut Commands, config: &SpawnConfig) -> Entity {
+  let mut character = commands.spawn((
+      CharacterColor(config.color),
+      Transform::from_translation(config.position.extend(0.0)),
+      config.size,
+      CharacterControllerBundle::new(config.size.collider())
+          .with_movement(config.movement.clone()),
+      // Bundles can only be so big, so the physics tuning is grouped together
+      (
+          layers::player(),
+          config.friction,
+          // The bouncier of the character and the surface wins, so that bouncy characters
+          // bounce off anything
+          Restitution::new(config.restitution).with_combine_rule(CoefficientCombine::Max),
+          ColliderDensity(2.0),
+          GravityScale(1.5),
+      ),
+      Damageable::new(config.health),
+      Invulnerable::new(config.invulnerability_secs),
+      MovingPlatformRider,
+      Stomp::default(),
+      StompAttack::default(),
+      PlacedMines::default(),
+      Grapple::default(),
+      TrajectoryPreview::default(),
+  ));
+  if let Some(glide) = &config.glide {
+      character.insert(glide.clone());
+  }
+  if let Some(ground_snap) = &config.ground_snap {
+      character.insert(ground_snap.clone());
+  }
+  if let Some(turn_boost) = &config.turn_boost {
+      character.insert(turn_boost.clone());
+  }
+  let mut primary = Entity::PLACEHOLDER;
+  let mut secondary = None;
+  character.with_children(|parent| {
+      primary = spawn_gun(parent, config, config.weapon, 0.0);
+      // Held behind the primary gun
+      secondary = config
+          .secondary_weapon
+          .map(|kind| spawn_gun(parent, config, kind, -0.1));
+  });
+  character.insert(Loadout { primary, secondary }).id()
+}
+
+// Gives a character a gun of `kind`, `depth` in front of it.
+fn spawn_gun(
+  parent: &mut ChildBuilder,
+  config: &SpawnConfig,
+  kind: WeaponKind,
+  depth: f32,
+) -> Entity {
+  let visual = GunVisual::new(kind);
+  let gun_color = config.color.darker(0.3);
+  let mut gun = parent.spawn((
+      visual.sprite(gun_color),
+      visual,
+      GunColor(gun_color),
+      Transform::from_xyz(0.0, 0.0, depth),
+      Gun,
+      Weapon {
+          muzzle_offset: config.size.muzzle_offset(),
+          ..Weapon::new(kind)
+      },
+      Spread::new(kind),
+  ));
+  if let Some(heat) = Heat::new(kind) {
+      gun.insert(heat);
+  }
+  if let Some(self_impulse) = SelfImpulse::new(kind) {
+      gun.insert(self_impulse);
+  }
+  gun.id()
+}
+
+// Gives newly spawned characters a capsule mesh of their size.
+pub fn dress_characters(
+  mut commands: Commands,
+  mut meshes: ResMut<Assets<Mesh>>,
+  mut materials: ResMut<Assets<ColorMaterial>>,
+  characters: Query<(Entity, &CharacterColor, Option<&CharacterSize>), Added<CharacterColor>>,
+) {
+  for (entity, color, size) in &characters {
+      let size = size.copied().unwrap_or_default();
+      commands.entity(entity).try_insert((
+          Mesh2d(meshes.add(size.mesh())),
+          MeshMaterial2d(materials.add(color.0)),
+      ));
+  }
+}
+
 pub fn spawn_character(
   mut commands: Commands,
   mut assignments: ResMut<PlayerAssignments>,
+  mut spectating: ResMut<Spectating>,
+  mut announcements: EventWriter<Announcement>,
+  keyboard_input: Res<ButtonInput<KeyCode>>,
+  bindings: Res<InputBindings>,
   gamepads: Query<(Entity, &Gamepad)>,
-  mut meshes: ResMut<Assets<Mesh>>,
-  mut materials: ResMut<Assets<ColorMaterial>>,
+  spawn_points: Res<SpawnPoints>,
+  match_config: Res<MatchConfig>,
+  colors: Res<PlayerColors>,
+  classes: Res<PlayerClasses>,
+  characters: Query<&Transform, With<CharacterController>>,
+) {
+  let mut occupied: Vec<Vec2> = characters
+      .iter()
+      .map(|transform| transform.translation.truncate())
+      .collect();
+  // Every device pressing its join button, or Start on a gamepad
+  let mut joining: Vec<InputDevice> = gamepads
+      .iter()
+      .filter(|(_, gamepad)| {
+          gamepad.any_just_pressed([bindings.gamepad.join, GamepadButton::Start])
+      })
+      .map(|(entity, _)| InputDevice::Gamepad(entity))
+      .collect();
+  if keyboard_input.just_pressed(bindings.keyboard.join) {
+      joining.push(InputDevice::Keyboard);
+  }
+  for device in joining {
+      let gid = assignments.device_id(device);
+      if assignments.players.contains_key(&gid) {
+          continue;
+      }
+      if assignments.players.len() >= match_config.max_players {
+          warn!("Player {} can't join, the game is full ({} players)", gid, match_config.max_players);
+          announcements.send(Announcement("The game is full".to_string()));
+          continue;
+      }
+      let spawn_point = spawn_points.pick(&occupied);
+      occupied.push(spawn_point);
+      let entity = spawn_controllable_character(
+          &mut commands,
+          &SpawnConfig {
+              position: spawn_point,
+              color: colors.color(gid),
+              invulnerability_secs: match_config.invulnerability_secs,
+              ..classes.class(gid).spawn_config()
+          },
+      );
+      commands.entity(entity).insert((PlayerId(gid), device));
+      assignments.players.insert(gid, entity);
+      spectating.players.remove(&gid);
+  }
+}
+
+// Switches between platforming and space movement on F6, and keeps every
+// character in the match's movement mode.
+pub fn apply_movement_mode(
+  keyboard_input: Res<ButtonInput<KeyCode>>,
+  mut match_config: ResMut<MatchConfig>,
+  mut world_gravity: ResMut<WorldGravity>,
+  mut characters: Query<&mut MovementMode>,
 ) {
-  for (entity, gamepad) in &gamepads {
-      let start_button = gamepad.get(GamepadButton::South).unwrap_or(0.0);
-      let gid = entity.index();
-      if start_button > 0.1 && !assignments.players.contains_key(&gid) {
-          let entity = commands
-              .spawn((
-                  Mesh2d(meshes.add(Capsule2d::new(12.5, 20.0))),
-                  MeshMaterial2d(materials.add(Color::srgb(0.9, 0.1, 0.1))),
-                  Transform::from_xyz(50.0, -100.0, 0.0),
-                  CharacterControllerBundle::new(Collider::capsule(12.5, 20.0)).with_movement(
-                      1250.0,
-                      0.92,
-                      800.0,
-                      Quat::IDENTITY,
-                      (30.0 as Scalar).to_radians(),
-                      0.0,
-                  ),
-                  //Friction::ZERO.with_combine_rule(CoefficientCombine::Min),
-                  Friction::new(0.8).with_dynamic_coefficient(0.8).with_static_coefficient(0.8),
-                  Restitution::ZERO.with_combine_rule(CoefficientCombine::Min),
-                  ColliderDensity(2.0),
-                  GravityScale(1.5),
-              ))
-              .with_children(|parent| {
-                  parent.spawn((
-                      Sprite {
-                          color: Color::srgb(0.2, 0.2, 0.2),
-                          custom_size: Some(Vec2::new(10.0, 40.0)),
-                          anchor: bevy::sprite::Anchor::TopCenter,
-                          ..default()
-                      },
-                      Transform::default(),
-                      Gun,
-                  ));
-              })
-              .id();
-          assignments.players.insert(gid, entity);
+  if keyboard_input.just_pressed(KeyCode::F6) {
+      match_config.movement_mode = match match_config.movement_mode {
+          MovementMode::Platformer => MovementMode::Space,
+          MovementMode::Space => MovementMode::Platformer,
+      };
+      // Space is weightless
+      if match_config.movement_mode == MovementMode::Space {
+          world_gravity.strength = 0.0;
       }
+      info!("Movement mode: {:?}", match_config.movement_mode);
+  }
+  for mut mode in &mut characters {
+      mode.set_if_neq(match_config.movement_mode);
   }
 }
 
+// How much of the end of its life a projectile spends fading out.
+const PROJECTILE_FADE_FRACTION: f32 = 0.25;
+
 pub fn move_objects(
   time: Res<Time>,
   mut commands: Commands,
-  mut query: Query<(Entity, &mut Transform, &mut Projectile)>,
+  mut query: Query<(Entity, &mut Projectile, Option<&mut Sprite>)>,
 ) {
-  for (entity, mut transform, mut projectile) in query.iter_mut() {
-      // Update position based on velocity
+  for (entity, mut projectile, sprite) in query.iter_mut() {
+      // Physics moves projectiles along, only their lifetime is counted down here
       let delta_time = time.delta_secs_f64().adjust_precision();
-      transform.translation += projectile.velocity.extend(0.0) * delta_time;
-
       if projectile.lifetime > 0.0 {
           projectile.lifetime -= delta_time;
+          // Fade out rather than popping out of existence
+          if let Some(mut sprite) = sprite {
+              let alpha = (projectile.life_fraction() / PROJECTILE_FADE_FRACTION).min(1.0);
+              sprite.color.set_alpha(alpha);
+          }
       } else {
           // Remove the projectile after its lifetime expires
           commands.entity(entity).despawn();
       }
   }
-}
\ No newline at end of file
+}
+
+// Removes a character from the game. Its player's slot is freed up so they can
+// join again to respawn, and they get to spectate in the meantime.
+pub fn kill_character(
+  commands: &mut Commands,
+  assignments: &mut PlayerAssignments,
+  spectating: &mut Spectating,
+  character: Entity,
+) {
+  if let Some(id) = assignments.player_id(character) {
+      assignments.players.remove(&id);
+      spectating.players.insert(id);
+  }
+  // Takes the gun, grapple joint and anything else attached to the character with it
+  commands.entity(character).despawn_recursive();
+}
+
+// Kills characters and removes projectiles that have left the `WorldBounds`.
+pub fn enforce_world_bounds(
+  mut commands: Commands,
+  bounds: Res<WorldBounds>,
+  mut damage: DamageWriter,
+  mut characters: Query<
+      (Entity, &Transform, &mut Damageable, Option<&LastHitBy>),
+      With<CharacterController>,
+  >,
+  projectiles: Query<(Entity, &Transform), With<Projectile>>,
+) {
+  for (entity, transform, mut damageable, last_hit_by) in &mut characters {
+      if !bounds.contains(transform.translation.truncate()) {
+          // Whoever knocked them out gets the kill
+          let killer = last_hit_by.map(|last_hit_by| last_hit_by.attacker);
+          damage.kill(entity, &mut damageable, killer);
+      }
+  }
+  for (entity, transform) in &projectiles {
+      if !bounds.contains(transform.translation.truncate()) {
+          commands.entity(entity).despawn();
+      }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::render::mesh::VertexAttributeValues;
+
+    use super::*;
+    use crate::player::{JumpImpulse, KEYBOARD_ID};
+
+    fn spawned_jump_impulse(config: SpawnConfig) -> JumpImpulse {
+        let mut world = World::new();
+        let character = world
+            .run_system_once(move |mut commands: Commands| {
+                spawn_controllable_character(&mut commands, &config)
+            })
+            .unwrap();
+        world.get::<JumpImpulse>(character).unwrap().clone()
+    }
+
+    #[test]
+    fn only_light_characters_turn_boost() {
+        for class in CharacterClass::ALL {
+            let mut world = World::new();
+            let config = class.spawn_config();
+            let character = world
+                .run_system_once(move |mut commands: Commands| {
+                    spawn_controllable_character(&mut commands, &config)
+                })
+                .unwrap();
+            let boosted = world.entity(character).contains::<TurnBoost>();
+            assert_eq!(boosted, class == CharacterClass::Light, "{class:?}");
+        }
+    }
+
+    #[test]
+    fn planet_mesh_stays_within_a_pixel_of_the_collider() {
+        let radius = 5000.0;
+        let mesh = planet_mesh(radius);
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("planet mesh has no positions");
+        };
+        // Skip the center, the rest go around the outline in order
+        let outline: Vec<Vec2> = positions
+            .iter()
+            .map(|position| Vec3::from(*position).truncate())
+            .filter(|position| *position != Vec2::ZERO)
+            .collect();
+        assert!(!outline.is_empty());
+        for (start, end) in outline.iter().zip(outline.iter().cycle().skip(1)) {
+            assert!((start.length() - radius).abs() < 0.01);
+            // The flat edges sag inwards the most halfway along
+            let sag = radius - ((*start + *end) / 2.0).length();
+            assert!((0.0..1.0).contains(&sag), "edge sags {sag} pixels");
+        }
+    }
+
+    #[test]
+    fn every_spawn_path_jumps_the_same() {
+        // Joining players, local or over the network, get their class's config
+        let classes = PlayerClasses::default();
+        let player = spawned_jump_impulse(classes.class(KEYBOARD_ID).spawn_config());
+        // Bots and mirrors of remote characters start from the defaults
+        let bot = spawned_jump_impulse(SpawnConfig::default());
+        let standard = spawned_jump_impulse(CharacterClass::Standard.spawn_config());
+        assert_eq!(player, bot);
+        assert_eq!(player, standard);
+        // The other presets jump differently on purpose
+        for class in [CharacterClass::Light, CharacterClass::Heavy] {
+            let mut classes = PlayerClasses::default();
+            classes.selected.insert(KEYBOARD_ID, class);
+            let picked = spawned_jump_impulse(classes.class(KEYBOARD_ID).spawn_config());
+            assert_eq!(picked, spawned_jump_impulse(class.spawn_config()));
+            assert_ne!(picked, standard, "{class:?}");
+        }
+    }
+
+    #[test]
+    fn killed_characters_take_their_guns_with_them() {
+        let mut world = World::new();
+        world.init_resource::<PlayerAssignments>();
+        world.init_resource::<Spectating>();
+        let character = world
+            .run_system_once(|mut commands: Commands| {
+                spawn_controllable_character(
+                    &mut commands,
+                    &SpawnConfig {
+                        secondary_weapon: Some(WeaponKind::Pistol),
+                        ..default()
+                    },
+                )
+            })
+            .unwrap();
+        let mut guns = world.query_filtered::<(), With<Gun>>();
+        assert_eq!(guns.iter(&world).count(), 2);
+
+        world
+            .run_system_once(
+                move |mut commands: Commands,
+                      mut assignments: ResMut<PlayerAssignments>,
+                      mut spectating: ResMut<Spectating>| {
+                    kill_character(&mut commands, &mut assignments, &mut spectating, character);
+                },
+            )
+            .unwrap();
+        assert!(world.get::<CharacterController>(character).is_none());
+        assert_eq!(guns.iter(&world).count(), 0);
+    }
+}
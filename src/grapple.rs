@@ -0,0 +1,133 @@
+use avian2d::{math::*, prelude::*};
+use bevy::prelude::*;
+
+use crate::player::{AimRotation, PlayerAction};
+
+pub struct GrapplePlugin;
+
+impl Plugin for GrapplePlugin {
+    fn build(&self, app: &mut App) {
+        // Tethers pull characters around, so they're handled on the fixed time step
+        app.add_systems(FixedUpdate, (grapple, reel_in_grapples).chain())
+            .add_systems(Update, draw_tethers);
+    }
+}
+
+// How far a grappling hook reaches.
+const GRAPPLE_RANGE: Scalar = 600.0;
+// How fast an attached tether is reeled in, in pixels per second.
+const REEL_SPEED: Scalar = 300.0;
+// The tether stops reeling in at this length.
+const MIN_TETHER_LENGTH: Scalar = 40.0;
+
+// A grappling hook that tethers a character to whatever it hits.
+#[derive(Component, Default)]
+pub struct Grapple {
+    // The joint holding the character, while attached.
+    pub attached: Option<Entity>,
+    // Where the hook is attached, in world space.
+    pub anchor: Vector,
+}
+
+// Shoots hooks along the aim and detaches them again on `PlayerAction`s.
+fn grapple(
+    mut commands: Commands,
+    mut movement_event_reader: EventReader<PlayerAction>,
+    spatial_query: SpatialQuery,
+    mut characters: Query<(&mut Grapple, &Position, &AimRotation)>,
+    bodies: Query<(&Position, &Rotation)>,
+) {
+    for event in movement_event_reader.read() {
+        match *event {
+            PlayerAction::Grapple(character) => {
+                let Ok((mut grapple, position, aim)) = characters.get_mut(character) else {
+                    continue;
+                };
+                if grapple.attached.is_some() {
+                    continue;
+                }
+                // Guns point down their local Y axis
+                let Ok(direction) = Dir2::new((aim.0 * Vec3::NEG_Y).truncate()) else {
+                    continue;
+                };
+                let Some(hit) = spatial_query.cast_ray(
+                    position.0,
+                    direction,
+                    GRAPPLE_RANGE,
+                    true,
+                    &SpatialQueryFilter::from_excluded_entities([character]),
+                ) else {
+                    continue;
+                };
+                let Ok((body_position, body_rotation)) = bodies.get(hit.entity) else {
+                    continue;
+                };
+                let anchor = position.0 + direction * hit.distance;
+                let local_anchor = body_rotation.inverse() * (anchor - body_position.0);
+                let joint = commands
+                    .spawn(
+                        DistanceJoint::new(character, hit.entity)
+                            .with_local_anchor_2(local_anchor)
+                            .with_limits(0.0, hit.distance),
+                    )
+                    // Goes away with the character
+                    .set_parent(character)
+                    .id();
+                grapple.attached = Some(joint);
+                grapple.anchor = anchor;
+            }
+            PlayerAction::ReleaseGrapple(character) => {
+                if let Ok((mut grapple, ..)) = characters.get_mut(character) {
+                    if let Some(joint) = grapple.attached.take() {
+                        // Recursive so that it's also taken out of the character's children
+                        commands.entity(joint).despawn_recursive();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Shortens attached tethers over time, pulling characters towards the anchor.
+fn reel_in_grapples(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut grapples: Query<&mut Grapple>,
+    mut joints: Query<&mut DistanceJoint>,
+    bodies: Query<(&Position, &Rotation)>,
+) {
+    let delta_time = time.delta_secs_f64().adjust_precision();
+    for mut grapple in &mut grapples {
+        let Some(joint_entity) = grapple.attached else {
+            continue;
+        };
+        let Ok(mut joint) = joints.get_mut(joint_entity) else {
+            grapple.attached = None;
+            continue;
+        };
+        // Let go if whatever the hook was attached to has been destroyed
+        let Ok((position, rotation)) = bodies.get(joint.entity2) else {
+            commands.entity(joint_entity).despawn_recursive();
+            grapple.attached = None;
+            continue;
+        };
+        // The anchor moves along with the body it's attached to
+        grapple.anchor = position.0 + *rotation * joint.local_anchor2;
+        if let Some(limits) = joint.length_limits.as_mut() {
+            limits.max = (limits.max - REEL_SPEED * delta_time).max(MIN_TETHER_LENGTH);
+        }
+    }
+}
+
+fn draw_tethers(grapples: Query<(&Grapple, &Transform)>, mut gizmos: Gizmos) {
+    for (grapple, transform) in &grapples {
+        if grapple.attached.is_some() {
+            gizmos.line_2d(
+                transform.translation.truncate(),
+                grapple.anchor,
+                Color::srgb(0.8, 0.8, 0.8),
+            );
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use avian2d::{math::*, prelude::*};
+use bevy::prelude::*;
+
+use crate::player::MovementMode;
+
+pub struct GravityPlugin;
+
+impl Plugin for GravityPlugin {
+    fn build(&self, app: &mut App) {
+        // Avian's uniform gravity only carries `WorldGravity`, on top of the pull of the planets
+        app.insert_resource(Gravity(Vector::ZERO))
+            .insert_resource(WorldGravity::default())
+            .add_systems(Update, (tune_world_gravity, apply_world_gravity).chain())
+            .add_systems(FixedUpdate, radial_gravity);
+    }
+}
+
+// Uniform gravity acting everywhere on top of the planets, off by default.
+// Can be tuned live: [ and ] change the strength, \ flips it and 0 turns it off.
+#[derive(Resource)]
+pub struct WorldGravity {
+    pub direction: Vector,
+    pub strength: Scalar,
+}
+
+impl Default for WorldGravity {
+    fn default() -> Self {
+        Self {
+            direction: Vector::NEG_Y,
+            strength: 0.0,
+        }
+    }
+}
+
+impl WorldGravity {
+    pub fn acceleration(&self) -> Vector {
+        self.direction.normalize_or_zero() * self.strength
+    }
+}
+
+// How much each press of a tuning key changes the world gravity strength.
+const WORLD_GRAVITY_STEP: Scalar = 100.0;
+
+// A body that pulls everything towards its center. The pull is `surface_gravity`
+// at `radius` and falls off with the inverse of the distance beyond it.
+#[derive(Component)]
+pub struct Planet {
+    pub radius: Scalar,
+    pub surface_gravity: Scalar,
+}
+
+impl Planet {
+    // The gravitational acceleration this planet causes at `point`, if it's centered at `center`.
+    pub fn gravity_at(&self, center: Vector, point: Vector) -> Vector {
+        let offset = center - point;
+        let distance = offset.length();
+        if distance <= Scalar::EPSILON {
+            return Vector::ZERO;
+        }
+        // Inside the planet the pull doesn't keep growing
+        offset / distance * self.surface_gravity * self.radius / distance.max(self.radius)
+    }
+}
+
+// The gravity currently acting on a body, summed over all planets. "Down" for it.
+#[derive(Component, Default)]
+pub struct LocalGravity(pub Vector);
+
+fn tune_world_gravity(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut world_gravity: ResMut<WorldGravity>,
+) {
+    if keyboard_input.just_pressed(KeyCode::BracketRight) {
+        world_gravity.strength += WORLD_GRAVITY_STEP;
+    }
+    if keyboard_input.just_pressed(KeyCode::BracketLeft) {
+        world_gravity.strength = (world_gravity.strength - WORLD_GRAVITY_STEP).max(0.0);
+    }
+    if keyboard_input.just_pressed(KeyCode::Backslash) {
+        world_gravity.direction = -world_gravity.direction;
+    }
+    if keyboard_input.just_pressed(KeyCode::Digit0) {
+        world_gravity.strength = 0.0;
+    }
+    if world_gravity.is_changed() && !world_gravity.is_added() {
+        info!(
+            "World gravity: {:.0} towards {:?}",
+            world_gravity.strength, world_gravity.direction
+        );
+    }
+}
+
+fn apply_world_gravity(world_gravity: Res<WorldGravity>, mut gravity: ResMut<Gravity>) {
+    if world_gravity.is_changed() {
+        gravity.0 = world_gravity.acceleration();
+    }
+}
+
+fn radial_gravity(
+    time: Res<Time>,
+    world_gravity: Res<Gravity>,
+    planets: Query<(Entity, &Planet, &Position)>,
+    mut bodies: Query<(
+        Entity,
+        &RigidBody,
+        &Position,
+        &mut LinearVelocity,
+        Option<&GravityScale>,
+        Option<&mut LocalGravity>,
+        Option<&MovementMode>,
+    )>,
+) {
+    let delta_time = time.delta_secs_f64().adjust_precision();
+    for (entity, rigid_body, position, mut linear_velocity, gravity_scale, local_gravity, mode) in
+        &mut bodies
+    {
+        if !rigid_body.is_dynamic() {
+            continue;
+        }
+        let gravity = planets
+            .iter()
+            .filter(|(planet_entity, ..)| *planet_entity != entity)
+            .fold(Vector::ZERO, |acc, (_, planet, center)| {
+                acc + planet.gravity_at(center.0, position.0)
+            });
+        if let Some(mut local_gravity) = local_gravity {
+            // Avian applies the uniform part itself, it only counts towards "down" here
+            local_gravity.0 = gravity + world_gravity.0;
+        }
+        // Characters in space float freely
+        if mode == Some(&MovementMode::Space) {
+            continue;
+        }
+        let scale = gravity_scale.map_or(1.0, |scale| scale.0);
+        linear_velocity.0 += gravity * scale * delta_time;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::testing::{physics_app, run_ticks};
+    use crate::weapons::{reset_projectile, Shot, WeaponKind};
+
+    #[test]
+    fn orbiter_shots_circle_the_planet() {
+        let mut app = physics_app();
+        app.add_systems(FixedUpdate, radial_gravity);
+        let planet = Planet {
+            radius: 100.0,
+            surface_gravity: 400.0,
+        };
+        // The pull falls off with distance, so this speed keeps a circular orbit anywhere
+        let kind = WeaponKind::Orbiter;
+        let speed = (planet.surface_gravity * planet.radius * kind.gravity_scale()).sqrt();
+        let world = app.world_mut();
+        world.spawn((planet, RigidBody::Static, Transform::default()));
+        let owner = world.spawn_empty().id();
+        let projectile = world
+            .run_system_once(move |mut commands: Commands| {
+                let mut projectile = commands.spawn_empty();
+                reset_projectile(
+                    &mut projectile,
+                    Shot {
+                        owner,
+                        transform: Transform::from_xyz(200.0, 0.0, 0.0),
+                        velocity: Vec2::new(0.0, speed),
+                        lifetime: 30.0,
+                        damage: 0.0,
+                        falloff: kind.falloff(),
+                        blast_radius: None,
+                        color: Color::WHITE,
+                        size: Vec2::splat(4.0),
+                        collider: Collider::circle(2.0),
+                        mass: 1.0,
+                        gravity_scale: kind.gravity_scale(),
+                    },
+                );
+                projectile.id()
+            })
+            .unwrap();
+
+        // A little over one time around
+        let ticks = (std::f32::consts::TAU * 200.0 / speed * 64.0 * 1.1) as usize;
+        let mut swept = 0.0;
+        let mut previous = Vec2::X;
+        for _ in 0..ticks {
+            run_ticks(&mut app, 1);
+            let position = app.world().get::<Position>(projectile).unwrap().0;
+            let distance = position.length();
+            assert!(
+                (160.0..240.0).contains(&distance),
+                "fell out of orbit at {distance}"
+            );
+            swept += previous.angle_to(position);
+            previous = position;
+        }
+        assert!(swept > std::f32::consts::TAU, "only went {swept} radians around");
+    }
+}
@@ -0,0 +1,98 @@
+use avian2d::{math::*, prelude::*};
+use bevy::prelude::*;
+
+use crate::triggers::TriggerZone;
+
+pub struct HazardsPlugin;
+
+impl Plugin for HazardsPlugin {
+    fn build(&self, app: &mut App) {
+        // Forces are consumed by the physics step, so they are applied on the fixed timestep.
+        app.add_systems(FixedUpdate, apply_environment_forces);
+    }
+}
+
+// An environmental hazard that pulls nearby dynamic bodies towards its center.
+// The pull is strongest at the center and fades out linearly towards `radius`,
+// bodies outside of the radius are unaffected.
+#[derive(Component)]
+pub struct GravityWell {
+    pub center: Vec2,
+    pub strength: Scalar,
+    pub radius: Scalar,
+}
+
+impl GravityWell {
+    // The force this well exerts on a body at `point`.
+    pub fn pull_at(&self, point: Vector) -> Vector {
+        let offset = self.center - point;
+        let distance = offset.length();
+        if distance > self.radius || distance <= Scalar::EPSILON {
+            return Vector::ZERO;
+        }
+        offset / distance * self.strength * (1.0 - distance / self.radius)
+    }
+}
+
+// An area such as a wind tunnel or an updraft that pushes every dynamic body inside it.
+// The area is the zone's collider. `force` is scaled by each body's mass, so light
+// projectiles and heavy characters are pushed along at the same rate.
+#[derive(Component)]
+#[require(TriggerZone)]
+pub struct ForceZone {
+    pub force: Vec2,
+}
+
+impl ForceZone {
+    // The force this zone exerts on `body` of `mass`, given the zone's occupants.
+    pub fn push_on(&self, trigger: &TriggerZone, body: Entity, mass: Scalar) -> Vector {
+        if trigger.occupants.contains(&body) {
+            self.force * mass
+        } else {
+            Vector::ZERO
+        }
+    }
+}
+
+// Applies the pull of gravity wells and the push of force zones to dynamic bodies.
+// Both are summed into a single `ExternalForce` so that neither overwrites the other.
+fn apply_environment_forces(
+    mut commands: Commands,
+    wells: Query<&GravityWell>,
+    zones: Query<(&ForceZone, &TriggerZone)>,
+    mut bodies: Query<(
+        Entity,
+        &RigidBody,
+        &Position,
+        &ComputedMass,
+        Option<&mut ExternalForce>,
+    )>,
+) {
+    if wells.is_empty() && zones.is_empty() {
+        return;
+    }
+    for (entity, rigid_body, position, mass, external_force) in &mut bodies {
+        if !rigid_body.is_dynamic() {
+            continue;
+        }
+        let pull = wells
+            .iter()
+            .fold(Vector::ZERO, |acc, well| acc + well.pull_at(position.0))
+            + zones.iter().fold(Vector::ZERO, |acc, (zone, trigger)| {
+                acc + zone.push_on(trigger, entity, mass.value())
+            });
+        if pull == Vector::ZERO {
+            continue;
+        }
+        match external_force {
+            Some(mut force) => {
+                force.apply_force(pull);
+            }
+            None => {
+                commands
+                    .entity(entity)
+                    .try_insert(ExternalForce::new(pull).with_persistence(false));
+            }
+        }
+    }
+}
@@ -0,0 +1,398 @@
+use bevy::prelude::*;
+
+use crate::ai::AiController;
+use crate::combat::{CombatSet, DamageEvent, DeathEvent};
+use crate::game::MatchConfig;
+use crate::lobby::PlayerColors;
+use crate::player::{CharacterController, PlayerAssignments, PlayerId, KEYBOARD_ID};
+use crate::round::{GameState, RoundTimer, SuddenDeath};
+use crate::scoring::{PlayerStats, Scores};
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Announcement>()
+            .init_resource::<DamageFlash>()
+            .add_systems(
+                Startup,
+                (
+                    spawn_kill_feed,
+                    spawn_round_clock,
+                    spawn_announcements,
+                    spawn_stats_panel,
+                    spawn_damage_vignette,
+                ),
+            )
+            .add_systems(Update, (update_round_clock, show_announcements))
+            .add_systems(OnEnter(GameState::GameOver), show_stats)
+            .add_systems(OnExit(GameState::GameOver), hide_stats)
+            // Read deaths while the characters involved are still around
+            .add_systems(
+                FixedUpdate,
+                push_kill_feed
+                    .after(CombatSet::Damage)
+                    .before(CombatSet::Cleanup),
+            )
+            .add_systems(
+                Update,
+                (fade_kill_feed, flash_on_damage, fade_damage_vignette).chain(),
+            );
+    }
+}
+
+// A short message shown to everyone in the middle of the screen, e.g. "The game is full".
+#[derive(Event)]
+pub struct Announcement(pub String);
+
+// How long an announcement stays on screen, in seconds.
+const ANNOUNCEMENT_SECS: f32 = 2.0;
+
+// How many kills the feed shows at once.
+const KILL_FEED_LEN: usize = 5;
+// How long a kill stays in the feed, in seconds.
+const KILL_FEED_SECS: f32 = 4.0;
+// How long a kill takes to fade out at the end of its time in the feed.
+const KILL_FEED_FADE_SECS: f32 = 1.0;
+
+// The list of recent kills in the top right corner.
+#[derive(Component)]
+struct KillFeed;
+
+#[derive(Component)]
+struct KillFeedEntry {
+    timer: Timer,
+}
+
+fn spawn_kill_feed(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            right: Val::Px(10.0),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::FlexEnd,
+            ..default()
+        },
+        KillFeed,
+    ));
+}
+
+// How a character is named in the HUD.
+fn character_label(
+    character: Entity,
+    players: &Query<&PlayerId>,
+    bots: &Query<(), With<AiController>>,
+) -> String {
+    if let Ok(player) = players.get(character) {
+        format!("P{}", player.0)
+    } else if bots.contains(character) {
+        "Bot".to_string()
+    } else {
+        "?".to_string()
+    }
+}
+
+fn push_kill_feed(
+    mut commands: Commands,
+    mut death_event_reader: EventReader<DeathEvent>,
+    colors: Res<PlayerColors>,
+    feeds: Query<(Entity, Option<&Children>), With<KillFeed>>,
+    characters: Query<(), With<CharacterController>>,
+    players: Query<&PlayerId>,
+    bots: Query<(), With<AiController>>,
+) {
+    let Ok((feed, children)) = feeds.get_single() else {
+        return;
+    };
+    let mut shown: Vec<Entity> = children.map_or(Vec::new(), |children| children.to_vec());
+    for death in death_event_reader.read() {
+        if !characters.contains(death.victim) {
+            continue;
+        }
+        let victim = character_label(death.victim, &players, &bots);
+        let (text, color) = match death.killer {
+            Some(killer) if killer != death.victim => (
+                format!("{} -> {}", character_label(killer, &players, &bots), victim),
+                players
+                    .get(killer)
+                    .map_or(Color::srgb(0.6, 0.6, 0.6), |player| colors.color(player.0)),
+            ),
+            _ => (format!("{} died", victim), Color::WHITE),
+        };
+        let entry = commands
+            .spawn((
+                Text::new(text),
+                TextFont {
+                    font_size: 18.0,
+                    ..default()
+                },
+                TextColor(color),
+                KillFeedEntry {
+                    timer: Timer::from_seconds(KILL_FEED_SECS, TimerMode::Once),
+                },
+            ))
+            .id();
+        commands.entity(feed).add_child(entry);
+        shown.push(entry);
+        // Drop the oldest kills to keep the feed short
+        while shown.len() > KILL_FEED_LEN {
+            commands.entity(shown.remove(0)).despawn_recursive();
+        }
+    }
+}
+
+fn fade_kill_feed(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut entries: Query<(Entity, &mut KillFeedEntry, &mut TextColor)>,
+) {
+    for (entity, mut entry, mut color) in &mut entries {
+        entry.timer.tick(time.delta());
+        if entry.timer.finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        let alpha = (entry.timer.remaining_secs() / KILL_FEED_FADE_SECS).min(1.0);
+        color.0.set_alpha(alpha);
+    }
+}
+
+// The time left in the round, at the top of the screen.
+#[derive(Component)]
+struct RoundClock;
+
+fn spawn_round_clock(mut commands: Commands) {
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                Text::default(),
+                TextFont {
+                    font_size: 28.0,
+                    ..default()
+                },
+                RoundClock,
+            ));
+        });
+}
+
+fn update_round_clock(
+    state: Res<State<GameState>>,
+    round_timer: Option<Res<RoundTimer>>,
+    sudden_death: Option<Res<SuddenDeath>>,
+    scores: Res<Scores>,
+    match_config: Res<MatchConfig>,
+    mut clocks: Query<&mut Text, With<RoundClock>>,
+) {
+    let text = match (state.get(), round_timer) {
+        (GameState::Lobby, _) => format!(
+            "{} (M to change)\nPress A or Enter to join, F1 or Select to remap",
+            match_config.mode.name()
+        ),
+        (GameState::Playing, None) => return,
+        (GameState::GameOver, _) => match scores.leader() {
+            Some(winner) => format!("P{} wins!", winner.0),
+            None => "Game over".to_string(),
+        },
+        (GameState::Playing, Some(_)) if sudden_death.is_some() => "SUDDEN DEATH".to_string(),
+        (GameState::Playing, Some(round_timer)) => {
+            let remaining = round_timer.0.remaining_secs().ceil() as u32;
+            format!("{}:{:02}", remaining / 60, remaining % 60)
+        }
+    };
+    for mut clock in &mut clocks {
+        clock.0.clone_from(&text);
+    }
+}
+
+#[derive(Component)]
+struct AnnouncementText {
+    timer: Timer,
+}
+
+fn spawn_announcements(mut commands: Commands) {
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(30.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                Text::default(),
+                TextFont {
+                    font_size: 32.0,
+                    ..default()
+                },
+                AnnouncementText {
+                    timer: Timer::from_seconds(ANNOUNCEMENT_SECS, TimerMode::Once),
+                },
+            ));
+        });
+}
+
+// Shows the latest announcement until it times out.
+fn show_announcements(
+    time: Res<Time>,
+    mut announcement_reader: EventReader<Announcement>,
+    mut texts: Query<(&mut Text, &mut AnnouncementText)>,
+) {
+    let latest = announcement_reader.read().last();
+    for (mut text, mut announcement) in &mut texts {
+        if let Some(Announcement(message)) = latest {
+            text.0.clone_from(message);
+            announcement.timer.reset();
+        } else if announcement.timer.tick(time.delta()).just_finished() {
+            text.0.clear();
+        }
+    }
+}
+
+// Everyone's lifetime stats, shown once a match is over.
+#[derive(Component)]
+struct StatsPanel;
+
+fn spawn_stats_panel(mut commands: Commands) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(40.0),
+                width: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            Visibility::Hidden,
+            StatsPanel,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::default(),
+                TextFont {
+                    font_size: 20.0,
+                    ..default()
+                },
+            ));
+        });
+}
+
+fn show_stats(
+    stats: Res<PlayerStats>,
+    mut panels: Query<(&mut Visibility, &Children), With<StatsPanel>>,
+    mut texts: Query<&mut Text>,
+) {
+    let mut players: Vec<_> = stats.0.iter().collect();
+    players.sort_by_key(|(player, _)| player.0);
+    let lines: Vec<String> = players
+        .into_iter()
+        .map(|(player, stats)| {
+            format!(
+                "P{}   kills {}   deaths {}   shots {}   hits {} ({:.0}%)   damage {:.0}",
+                player.0,
+                stats.kills,
+                stats.deaths,
+                stats.shots_fired,
+                stats.hits,
+                stats.accuracy() * 100.0,
+                stats.damage_dealt,
+            )
+        })
+        .collect();
+    for (mut visibility, children) in &mut panels {
+        *visibility = Visibility::Inherited;
+        for child in children {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.0 = lines.join("\n");
+            }
+        }
+    }
+}
+
+fn hide_stats(mut panels: Query<&mut Visibility, With<StatsPanel>>) {
+    for mut visibility in &mut panels {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+// How much of the vignette's full strength a point of damage adds.
+const DAMAGE_FLASH_PER_POINT: f32 = 1.0 / 40.0;
+// How fast the vignette fades out, in full strengths per second.
+const DAMAGE_FLASH_FADE: f32 = 1.5;
+// How opaque the vignette is at full strength.
+const DAMAGE_FLASH_MAX_ALPHA: f32 = 0.6;
+
+// How strongly the screen edges flash red after the primary player is hurt, 0..1.
+// The primary player is the keyboard player, or the first gamepad player if nobody
+// is on the keyboard.
+#[derive(Resource, Default)]
+pub struct DamageFlash {
+    pub intensity: f32,
+}
+
+// The red border around the screen that `DamageFlash` drives.
+#[derive(Component)]
+struct DamageVignette;
+
+fn spawn_damage_vignette(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            border: UiRect::all(Val::Px(48.0)),
+            ..default()
+        },
+        BorderColor(Color::NONE),
+        // Drawn above the rest of the HUD
+        GlobalZIndex(10),
+        DamageVignette,
+    ));
+}
+
+// The character of the keyboard player, or of the first gamepad player if nobody is on
+// the keyboard.
+pub fn primary_player(assignments: &PlayerAssignments) -> Option<Entity> {
+    assignments.players.get(&KEYBOARD_ID).copied().or_else(|| {
+        assignments
+            .players
+            .iter()
+            .min_by_key(|(id, _)| **id)
+            .map(|(_, character)| *character)
+    })
+}
+
+fn flash_on_damage(
+    mut damage_event_reader: EventReader<DamageEvent>,
+    assignments: Res<PlayerAssignments>,
+    mut flash: ResMut<DamageFlash>,
+) {
+    let primary = primary_player(&assignments);
+    for damage in damage_event_reader.read() {
+        if Some(damage.target) == primary {
+            flash.intensity = (flash.intensity + damage.total() * DAMAGE_FLASH_PER_POINT).min(1.0);
+        }
+    }
+}
+
+fn fade_damage_vignette(
+    time: Res<Time>,
+    mut flash: ResMut<DamageFlash>,
+    mut vignettes: Query<&mut BorderColor, With<DamageVignette>>,
+) {
+    if flash.intensity <= 0.0 {
+        return;
+    }
+    flash.intensity = (flash.intensity - DAMAGE_FLASH_FADE * time.delta_secs()).max(0.0);
+    for mut border in &mut vignettes {
+        border.0 = Color::srgba(0.8, 0.0, 0.0, flash.intensity * DAMAGE_FLASH_MAX_ALPHA);
+    }
+}
@@ -1,52 +1,229 @@
-use avian2d::{math::*, prelude::*};
-use bevy::prelude::*;
+use avian2d::math::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
 
-use crate::weapons::Gun;
+use crate::bindings::InputBindings;
 use crate::player::{
-  CharacterControllerBundle,
   PlayerAssignments,
   PlayerAction,
+  KEYBOARD_ID,
 };
+use crate::replay::ReplayTick;
+use crate::weapons::WeaponSlot;
+
+// How far the right stick has to be pushed to count as aiming. Below this the stick is
+// on its way back to center, and the last aim is kept.
+const AIM_DEADZONE: f32 = 0.3;
+
+// Holds live input back by `delay` fixed updates before it goes out as `PlayerAction`s.
+// In networked play the other players' actions reach the server a few ticks after they
+// were made, so delaying the local ones by about as much makes everyone's input take
+// effect equally late. The price is that the game feels that much less responsive, so
+// the delay is best kept as low as the connection allows.
+//
+// Held inputs are sent every frame, and there can be more frames than fixed updates, so
+// each tick keeps only the latest of each kind of action per character. That way a tick
+// gets the same input however many frames ran before it. When frames are further apart
+// than ticks, the ticks no frame sampled input for get the inputs held on the one before.
+#[derive(Resource)]
+pub struct InputBuffer {
+    delay: u32,
+    // Actions waiting to go out, with the tick they're due on.
+    pending: Vec<(u32, PlayerAction)>,
+    // The ticks frames have sampled input for, that haven't come due yet.
+    sampled: Vec<u32>,
+    // The held inputs that went out on the latest sampled tick.
+    held: Vec<PlayerAction>,
+    // Every action as it was made, for the network to pass on without the delay. Only
+    // kept when something takes them, see `forwarding`.
+    outgoing: Option<Vec<PlayerAction>>,
+}
+
+impl Default for InputBuffer {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl InputBuffer {
+    pub fn new(delay: u32) -> Self {
+        Self {
+            delay,
+            pending: Vec::new(),
+            sampled: Vec::new(),
+            held: Vec::new(),
+            outgoing: None,
+        }
+    }
+
+    // Also keeps every action as it's made, to be taken with `take_outgoing`.
+    pub fn forwarding(mut self) -> Self {
+        self.outgoing = Some(Vec::new());
+        self
+    }
+
+    pub fn delay(&self) -> u32 {
+        self.delay
+    }
+
+    // Notes that a frame sampled input for fixed update `tick`, whether any was made or not.
+    pub fn sample(&mut self, tick: u32) {
+        let due = tick.wrapping_add(self.delay);
+        if !self.sampled.contains(&due) {
+            self.sampled.push(due);
+        }
+    }
+
+    // Holds `action`, made for fixed update `tick`, until `delay` ticks later.
+    pub fn push(&mut self, tick: u32, action: PlayerAction) {
+        if let Some(outgoing) = &mut self.outgoing {
+            outgoing.push(action.clone());
+        }
+        let due = tick.wrapping_add(self.delay);
+        let queued = self
+            .pending
+            .iter_mut()
+            .find(|(queued_due, queued)| *queued_due == due && queued.replaces(&action));
+        match queued {
+            Some((_, queued)) => *queued = action,
+            None => self.pending.push((due, action)),
+        }
+    }
+
+    // Takes out the actions due on `tick`, in the order they were first made. Anything
+    // that was due earlier and somehow missed its tick goes out too.
+    pub fn take_due(&mut self, tick: u32) -> Vec<PlayerAction> {
+        // Ticks wrap around, so compare how far apart they are
+        let (due, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|(due, _)| (tick.wrapping_sub(*due) as i32) >= 0);
+        self.pending = pending;
+        let mut actions: Vec<PlayerAction> = due.into_iter().map(|(_, action)| action).collect();
+        let sampled = self.sampled.len();
+        self.sampled.retain(|due| (tick.wrapping_sub(*due) as i32) < 0);
+        if self.sampled.len() < sampled {
+            self.held = actions.iter().filter(|action| action.is_held()).cloned().collect();
+        } else {
+            // No frame ran in time to sample input for this tick, so whatever was held
+            // on the one before still is
+            for held in &self.held {
+                if !actions.iter().any(|action| action.replaces(held)) {
+                    actions.push(held.clone());
+                }
+            }
+        }
+        actions
+    }
+
+    pub fn take_outgoing(&mut self) -> Vec<PlayerAction> {
+        self.outgoing.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+}
+
+// Sends the `PlayerAction`s of live input through the `InputBuffer`, so that they're
+// applied on a fixed update.
+#[derive(SystemParam)]
+pub struct ActionWriter<'w> {
+    buffer: ResMut<'w, InputBuffer>,
+    tick: Res<'w, ReplayTick>,
+}
+
+impl ActionWriter<'_> {
+    pub fn send(&mut self, action: PlayerAction) {
+        // Input made after this tick goes into the next one
+        let tick = self.tick.0.wrapping_add(1);
+        self.buffer.push(tick, action);
+    }
+}
+
+// Marks the coming tick as sampled, before any input is sent this frame.
+pub fn sample_input(tick: Res<ReplayTick>, mut buffer: ResMut<InputBuffer>) {
+  buffer.sample(tick.0.wrapping_add(1));
+}
+
+// Sends the buffered actions due this tick, at its start like replayed ones.
+pub fn release_delayed_actions(
+  tick: Res<ReplayTick>,
+  mut buffer: ResMut<InputBuffer>,
+  mut action_writer: EventWriter<PlayerAction>,
+) {
+  action_writer.send_batch(buffer.take_due(tick.0));
+}
+
+// The direction the right stick aims in, if it's pushed far enough to count.
+fn aim_from_stick(stick: Vec2) -> Option<Vec2> {
+  (stick.length() > AIM_DEADZONE).then_some(stick)
+}
 
 pub fn gamepad_input(
-  mut movement_event_writer: EventWriter<PlayerAction>,
+  mut movement_event_writer: ActionWriter,
   assignments: Res<PlayerAssignments>,
+  bindings: Res<InputBindings>,
   gamepads: Query<(Entity, &Gamepad)>,
 ) {
+  let bindings = &bindings.gamepad;
   for (entity, gamepad) in &gamepads {
-      let gid = entity.index();
-      if let Some(entity) = assignments.players.get(&gid) {
+      let Some(gid) = assignments.gamepads.get(&entity) else {
+          continue;
+      };
+      if let Some(entity) = assignments.players.get(gid) {
           // Movement
           let x = gamepad.get(GamepadAxis::LeftStickX).unwrap_or(0.0);
           if x.abs() > 0.01 {
               movement_event_writer.send(PlayerAction::Move(*entity, x.into()));
           }
-          let jump = gamepad.get(GamepadButton::South).unwrap_or(0.0);
+          let jump = gamepad.get(bindings.jump).unwrap_or(0.0);
           if jump > 0.1 {
               movement_event_writer.send(PlayerAction::Jump(*entity));
+              movement_event_writer.send(PlayerAction::Glide(*entity));
+          }
+          // Pressing jump while holding down slams the character to the ground
+          if gamepad.left_stick().y < -0.5 && gamepad.just_pressed(bindings.jump) {
+              movement_event_writer.send(PlayerAction::Stomp(*entity));
           }
           // Aiming
           let rx = gamepad.get(GamepadAxis::RightStickX).unwrap_or(0.0);
           let ry = gamepad.get(GamepadAxis::RightStickY).unwrap_or(0.0);
-          if rx.abs() > 0.01 || ry.abs() > 0.01 {
-              movement_event_writer.send(PlayerAction::Aim(*entity, rx, ry));
+          if let Some(aim) = aim_from_stick(Vec2::new(rx, ry)) {
+              movement_event_writer.send(PlayerAction::Aim(*entity, aim.x, aim.y));
           }
-          let fire = gamepad.get(GamepadButton::RightTrigger).unwrap_or(0.0);
+          if gamepad.just_pressed(bindings.grapple) {
+              movement_event_writer.send(PlayerAction::Grapple(*entity));
+          }
+          if gamepad.just_released(bindings.grapple) {
+              movement_event_writer.send(PlayerAction::ReleaseGrapple(*entity));
+          }
+          if gamepad.just_pressed(GamepadButton::West) {
+              movement_event_writer.send(PlayerAction::Detonate(*entity));
+          }
+          if gamepad.pressed(bindings.walk) {
+              movement_event_writer.send(PlayerAction::Walk(*entity));
+          }
+          if gamepad.just_pressed(GamepadButton::Select) {
+              movement_event_writer.send(PlayerAction::TogglePreview(*entity));
+          }
+          let fire = gamepad.get(bindings.fire).unwrap_or(0.0);
           if fire > 0.1 {
-              movement_event_writer.send(PlayerAction::Fire(*entity));
+              movement_event_writer.send(PlayerAction::Fire(*entity, WeaponSlot::Primary, fire.into()));
+          }
+          let secondary_fire = gamepad.get(bindings.secondary_fire).unwrap_or(0.0);
+          if secondary_fire > 0.1 {
+              movement_event_writer.send(PlayerAction::Fire(
+                  *entity,
+                  WeaponSlot::Secondary,
+                  secondary_fire.into(),
+              ));
           }
       }
   }
 }
 
 pub fn keyboard_input(
-  mut commands: Commands,
-  mut movement_event_writer: EventWriter<PlayerAction>,
+  mut movement_event_writer: ActionWriter,
   keyboard_input: Res<ButtonInput<KeyCode>>,
-  mut assignments: ResMut<PlayerAssignments>,
-  mut meshes: ResMut<Assets<Mesh>>,
-  mut materials: ResMut<Assets<ColorMaterial>>,
+  assignments: Res<PlayerAssignments>,
+  bindings: Res<InputBindings>,
 ) {
+  let bindings = &bindings.keyboard;
   let left = keyboard_input.any_pressed([KeyCode::KeyA, KeyCode::ArrowLeft]);
   let right = keyboard_input.any_pressed([KeyCode::KeyD, KeyCode::ArrowRight]);
 
@@ -54,58 +231,221 @@ pub fn keyboard_input(
   let direction = horizontal as Scalar;
 
   if direction != 0.0 {
-      // Assuming the player entity is the first one in the assignments
-      if let Some(entity) = assignments.players.values().next() {
+      if let Some(entity) = assignments.players.get(&KEYBOARD_ID) {
           movement_event_writer.send(PlayerAction::Move(*entity, direction));
       }
   }
 
-  if keyboard_input.just_pressed(KeyCode::Space) {
-      if let Some(entity) = assignments.players.values().next() {
+  if keyboard_input.just_pressed(bindings.jump) {
+      if let Some(entity) = assignments.players.get(&KEYBOARD_ID) {
           movement_event_writer.send(PlayerAction::Jump(*entity));
+          if keyboard_input.any_pressed([KeyCode::KeyS, KeyCode::ArrowDown]) {
+              movement_event_writer.send(PlayerAction::Stomp(*entity));
+          }
       }
   }
 
-  if keyboard_input.just_pressed(KeyCode::KeyF) {
-      if let Some(entity) = assignments.players.values().next() {
-          movement_event_writer.send(PlayerAction::Aim(*entity, 0.5, 0.5));
-          movement_event_writer.send(PlayerAction::Fire(*entity));
+  if keyboard_input.just_pressed(bindings.fire) {
+      if let Some(entity) = assignments.players.get(&KEYBOARD_ID) {
+          movement_event_writer.send(PlayerAction::Fire(*entity, WeaponSlot::Primary, 1.0));
       }
   }
 
-  if keyboard_input.just_pressed(KeyCode::Enter) {
-      let entity = commands
-          .spawn((
-              Mesh2d(meshes.add(Capsule2d::new(12.5, 20.0))),
-              MeshMaterial2d(materials.add(Color::srgb(0.9, 0.1, 0.1))),
-              Transform::from_xyz(50.0, -100.0, 0.0),
-              CharacterControllerBundle::new(Collider::capsule(12.5, 20.0)).with_movement(
-                  1250.0,
-                  0.92,
-                  800.0,
-                  Quat::IDENTITY,
-                  (30.0 as Scalar).to_radians(),
-                  0.0,
-              ),
-              Friction::new(0.4).with_dynamic_coefficient(0.6).with_static_coefficient(0.6),
-              //Friction::ZERO.with_combine_rule(CoefficientCombine::Min),
-              Restitution::ZERO.with_combine_rule(CoefficientCombine::Min),
-              ColliderDensity(2.0),
-              GravityScale(1.5),
-          ))
-          .with_children(|parent| {
-              parent.spawn((
-                  Sprite {
-                      color: Color::srgb(0.2, 0.2, 0.2),
-                      custom_size: Some(Vec2::new(10.0, 40.0)),
-                      anchor: bevy::sprite::Anchor::TopCenter,
-                      ..default()
-                  },
-                  Transform::default(),
-                  Gun,
-              ));
-          })
-          .id();
-      assignments.players.insert(5, entity);
+  if keyboard_input.just_pressed(KeyCode::KeyG) {
+      if let Some(entity) = assignments.players.get(&KEYBOARD_ID) {
+          movement_event_writer.send(PlayerAction::Fire(*entity, WeaponSlot::Secondary, 1.0));
+      }
+  }
+
+  // Aiming with IJKL. The gun keeps pointing the same way once they're let go.
+  let aim_x = keyboard_input.pressed(KeyCode::KeyL) as i8
+      - keyboard_input.pressed(KeyCode::KeyJ) as i8;
+  let aim_y = keyboard_input.pressed(KeyCode::KeyI) as i8
+      - keyboard_input.pressed(KeyCode::KeyK) as i8;
+  if aim_x != 0 || aim_y != 0 {
+      if let Some(entity) = assignments.players.get(&KEYBOARD_ID) {
+          movement_event_writer.send(PlayerAction::Aim(*entity, aim_x as Scalar, aim_y as Scalar));
+      }
+  }
+
+  if let Some(entity) = assignments.players.get(&KEYBOARD_ID) {
+      if keyboard_input.pressed(bindings.jump) {
+          movement_event_writer.send(PlayerAction::Glide(*entity));
+      }
+      if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+          movement_event_writer.send(PlayerAction::Walk(*entity));
+      }
+      if keyboard_input.just_pressed(KeyCode::KeyQ) {
+          movement_event_writer.send(PlayerAction::Detonate(*entity));
+      }
+      if keyboard_input.just_pressed(KeyCode::KeyT) {
+          movement_event_writer.send(PlayerAction::TogglePreview(*entity));
+      }
+      if keyboard_input.just_pressed(KeyCode::KeyE) {
+          movement_event_writer.send(PlayerAction::Grapple(*entity));
+      }
+      if keyboard_input.just_released(KeyCode::KeyE) {
+          movement_event_writer.send(PlayerAction::ReleaseGrapple(*entity));
+      }
   }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::input::gamepad::GamepadInput;
+
+    use super::*;
+
+    // Runs `gamepad_input` once for a gamepad with `inputs` held at the given values and
+    // `pressed` just pressed, and returns the actions it sent for its character.
+    fn gamepad_actions(
+        inputs: &[(GamepadInput, f32)],
+        pressed: &[GamepadButton],
+    ) -> Vec<PlayerAction> {
+        let mut world = World::new();
+        world.init_resource::<InputBindings>();
+        world.init_resource::<InputBuffer>();
+        world.init_resource::<ReplayTick>();
+        let mut gamepad = Gamepad::default();
+        for (input, value) in inputs {
+            gamepad.analog_mut().set(*input, *value);
+        }
+        for button in pressed {
+            gamepad.digital_mut().press(*button);
+        }
+        let gamepad = world.spawn(gamepad).id();
+        let mut assignments = PlayerAssignments::default();
+        assignments.gamepads.insert(gamepad, 1);
+        assignments.players.insert(1, character());
+        world.insert_resource(assignments);
+        world.run_system_once(gamepad_input).unwrap();
+        world.resource_mut::<InputBuffer>().take_due(1)
+    }
+
+    #[test]
+    fn analog_triggers_fire_with_their_pressure() {
+        let actions = gamepad_actions(&[(GamepadButton::RightTrigger2.into(), 0.5)], &[]);
+        assert!(actions.contains(&PlayerAction::Fire(character(), WeaponSlot::Primary, 0.5)));
+    }
+
+    #[test]
+    fn each_trigger_fires_its_own_gun() {
+        let actions = gamepad_actions(
+            &[
+                (GamepadButton::LeftTrigger2.into(), 0.25),
+                (GamepadButton::RightTrigger2.into(), 1.0),
+            ],
+            &[],
+        );
+        assert!(actions.contains(&PlayerAction::Fire(character(), WeaponSlot::Secondary, 0.25)));
+        assert!(actions.contains(&PlayerAction::Fire(character(), WeaponSlot::Primary, 1.0)));
+        assert!(!actions.contains(&PlayerAction::Grapple(character())));
+
+        let actions = gamepad_actions(&[], &[GamepadButton::RightTrigger]);
+        assert_eq!(actions, vec![PlayerAction::Grapple(character())]);
+    }
+
+    #[test]
+    fn walking_follows_the_binding() {
+        let walk = InputBindings::default().gamepad.walk;
+        let actions = gamepad_actions(&[], &[walk]);
+        assert_eq!(actions, vec![PlayerAction::Walk(character())]);
+        // The bumper doesn't walk, and neither does the second gun's trigger
+        let actions = gamepad_actions(
+            &[(GamepadButton::LeftTrigger2.into(), 1.0)],
+            &[GamepadButton::LeftTrigger],
+        );
+        assert!(!actions.contains(&PlayerAction::Walk(character())));
+    }
+
+    #[test]
+    fn released_aim_stick_doesnt_aim() {
+        assert_eq!(aim_from_stick(Vec2::ZERO), None);
+        assert_eq!(aim_from_stick(Vec2::new(0.2, -0.2)), None);
+        assert_eq!(aim_from_stick(Vec2::new(0.0, AIM_DEADZONE)), None);
+        assert_eq!(aim_from_stick(Vec2::new(0.0, 0.5)), Some(Vec2::new(0.0, 0.5)));
+        assert_eq!(aim_from_stick(Vec2::new(-1.0, 0.0)), Some(Vec2::new(-1.0, 0.0)));
+    }
+
+    fn character() -> Entity {
+        Entity::from_raw(1)
+    }
+
+    #[test]
+    fn delayed_actions_come_due_after_the_delay() {
+        let mut buffer = InputBuffer::new(3);
+        buffer.push(10, PlayerAction::Jump(character()));
+        for tick in 10..13 {
+            assert!(buffer.take_due(tick).is_empty(), "released early on tick {tick}");
+        }
+        let due = buffer.take_due(13);
+        assert!(matches!(due.as_slice(), [PlayerAction::Jump(_)]));
+        assert!(buffer.take_due(14).is_empty());
+    }
+
+    #[test]
+    fn delayed_actions_survive_the_tick_wrapping_around() {
+        let mut buffer = InputBuffer::new(2);
+        buffer.push(u32::MAX - 1, PlayerAction::Jump(character()));
+        assert!(buffer.take_due(u32::MAX - 1).is_empty());
+        assert!(buffer.take_due(u32::MAX).is_empty());
+        let due = buffer.take_due(0);
+        assert!(matches!(due.as_slice(), [PlayerAction::Jump(_)]));
+    }
+
+    #[test]
+    fn without_a_delay_actions_are_due_on_their_own_tick() {
+        let mut buffer = InputBuffer::default();
+        buffer.push(5, PlayerAction::Jump(character()));
+        assert_eq!(buffer.take_due(5).len(), 1);
+    }
+
+    #[test]
+    fn each_tick_keeps_the_latest_of_each_held_input() {
+        let mut buffer = InputBuffer::new(1);
+        // Three frames ran before the tick
+        buffer.push(7, PlayerAction::Move(character(), 1.0));
+        buffer.push(7, PlayerAction::Fire(character(), WeaponSlot::Primary, 0.5));
+        buffer.push(7, PlayerAction::Move(character(), -0.5));
+        buffer.push(7, PlayerAction::Fire(character(), WeaponSlot::Secondary, 1.0));
+        buffer.push(7, PlayerAction::Move(character(), 0.25));
+        buffer.push(7, PlayerAction::Move(Entity::from_raw(2), 1.0));
+        let due = buffer.take_due(8);
+        assert_eq!(due.len(), 4);
+        assert!(matches!(due[0], PlayerAction::Move(_, direction) if direction == 0.25));
+        assert!(matches!(due[1], PlayerAction::Fire(_, WeaponSlot::Primary, _)));
+        assert!(matches!(due[2], PlayerAction::Fire(_, WeaponSlot::Secondary, _)));
+        assert_eq!(due[3].character(), Entity::from_raw(2));
+    }
+
+    #[test]
+    fn ticks_no_frame_sampled_keep_the_held_inputs() {
+        let mut buffer = InputBuffer::new(0);
+        buffer.sample(1);
+        buffer.push(1, PlayerAction::Move(character(), 1.0));
+        buffer.push(1, PlayerAction::Jump(character()));
+        assert_eq!(buffer.take_due(1).len(), 2);
+        // A slow frame, two ticks ran before the next one. Only the held move carries over.
+        let repeated = buffer.take_due(2);
+        assert!(matches!(repeated.as_slice(), [PlayerAction::Move(_, _)]));
+        // Once a frame samples nothing, nothing is held any more
+        buffer.sample(3);
+        assert!(buffer.take_due(3).is_empty());
+        assert!(buffer.take_due(4).is_empty());
+    }
+
+    #[test]
+    fn outgoing_actions_are_only_kept_when_forwarding() {
+        let mut buffer = InputBuffer::new(2);
+        buffer.push(1, PlayerAction::Jump(character()));
+        assert!(buffer.take_outgoing().is_empty());
+
+        let mut buffer = InputBuffer::new(2).forwarding();
+        buffer.push(1, PlayerAction::Move(character(), 1.0));
+        buffer.push(1, PlayerAction::Move(character(), 1.0));
+        // Passed on as made, before any delay or merging
+        assert_eq!(buffer.take_outgoing().len(), 2);
+        assert!(buffer.take_outgoing().is_empty());
+    }
+}
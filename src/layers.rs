@@ -0,0 +1,52 @@
+use avian2d::prelude::*;
+
+// The physics layers everything collides on. Spawn code picks the layers for an
+// entity with the functions below, which keeps who collides with what in one place.
+// Entities without `CollisionLayers` end up on the `World` layer and collide with everything.
+#[derive(PhysicsLayer, Clone, Copy, Debug, Default)]
+pub enum GameLayer {
+    // Planets, ledges and other level geometry.
+    #[default]
+    World,
+    // Characters, and dummies standing in for them.
+    Player,
+    Projectile,
+    // Loose objects: crates, barrels, debris and the like.
+    Item,
+}
+
+// Level geometry blocks everything.
+pub fn world() -> CollisionLayers {
+    CollisionLayers::new(GameLayer::World, LayerMask::ALL)
+}
+
+// Characters bump into everything. Shots never hit their own shooter, but that's
+// handled in `projectile_damage` since layers can't tell characters apart.
+pub fn player() -> CollisionLayers {
+    CollisionLayers::new(GameLayer::Player, LayerMask::ALL)
+}
+
+// Projectiles hit everything but each other, so crossing shots and a burst of
+// fragments don't knock each other out of the air.
+pub fn projectile() -> CollisionLayers {
+    CollisionLayers::new(
+        GameLayer::Projectile,
+        [GameLayer::World, GameLayer::Player, GameLayer::Item],
+    )
+}
+
+// Crates, barrels and other props get pushed and shot at like anything else.
+pub fn item() -> CollisionLayers {
+    CollisionLayers::new(GameLayer::Item, LayerMask::ALL)
+}
+
+// Pickups only notice characters touching them.
+pub fn pickup() -> CollisionLayers {
+    CollisionLayers::new(GameLayer::Item, [GameLayer::Player])
+}
+
+// Debris only rests on the ground and other items, so that it doesn't trip up
+// characters or soak up shots.
+pub fn debris() -> CollisionLayers {
+    CollisionLayers::new(GameLayer::Item, [GameLayer::World, GameLayer::Item])
+}
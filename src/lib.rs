@@ -0,0 +1,36 @@
+//! A 2D platformer shooter on a small planet, built around a dynamic character controller.
+//!
+//! The binary wires all of the plugins together, but they can be embedded in another
+//! game as well. See [`game::spawn_controllable_character`] for spawning characters.
+
+pub mod ai;
+pub mod bindings;
+pub mod camera;
+pub mod combat;
+pub mod debug;
+pub mod decals;
+pub mod game;
+pub mod grapple;
+pub mod gravity;
+pub mod hazards;
+pub mod hud;
+pub mod input;
+pub mod layers;
+pub mod lobby;
+#[cfg(feature = "net")]
+pub mod net;
+pub mod player;
+pub mod powerups;
+pub mod replay;
+pub mod rng;
+pub mod round;
+pub mod rumble;
+pub mod scoring;
+pub mod snapshot;
+pub mod stress;
+#[cfg(test)]
+mod testing;
+pub mod training;
+pub mod triggers;
+pub mod trajectory;
+pub mod weapons;
@@ -0,0 +1,165 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+use crate::bindings::not_remapping;
+use crate::game::{CharacterClass, MatchConfig};
+use crate::hud::Announcement;
+use crate::player::{PlayerAssignments, KEYBOARD_ID};
+use crate::round::GameState;
+
+pub struct LobbyPlugin;
+
+impl Plugin for LobbyPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PlayerColors::default())
+            .insert_resource(PlayerClasses::default())
+            .add_systems(
+                Update,
+                (
+                    select_player_color,
+                    select_player_class,
+                    select_game_mode.run_if(in_state(GameState::Lobby)),
+                )
+                    // The keys being bound shouldn't change anything else
+                    .run_if(not_remapping),
+            );
+    }
+}
+
+// The colors players can pick from, and what each of them has picked so far.
+#[derive(Resource)]
+pub struct PlayerColors {
+    pub palette: Vec<Color>,
+    // Index into `palette`, keyed like `PlayerAssignments`.
+    pub selected: HashMap<u32, usize>,
+}
+
+impl Default for PlayerColors {
+    fn default() -> Self {
+        Self {
+            palette: vec![
+                Color::srgb(0.9, 0.1, 0.1),
+                Color::srgb(0.1, 0.4, 0.9),
+                Color::srgb(0.1, 0.8, 0.2),
+                Color::srgb(0.95, 0.8, 0.1),
+                Color::srgb(0.8, 0.2, 0.8),
+                Color::srgb(0.1, 0.8, 0.8),
+                Color::srgb(0.95, 0.5, 0.1),
+                Color::srgb(0.9, 0.9, 0.9),
+            ],
+            selected: HashMap::new(),
+        }
+    }
+}
+
+impl PlayerColors {
+    // The color of player `id`. Players who haven't picked one get a color based
+    // on their id, so that they are still told apart.
+    pub fn color(&self, id: u32) -> Color {
+        let index = self
+            .selected
+            .get(&id)
+            .copied()
+            .unwrap_or(id as usize);
+        self.palette[index % self.palette.len()]
+    }
+
+    // Moves player `id` `step` colors forward (or backward) in the palette.
+    pub fn cycle(&mut self, id: u32, step: isize) {
+        let len = self.palette.len() as isize;
+        let current = self.selected.get(&id).copied().unwrap_or(id as usize) as isize;
+        self.selected.insert(id, (current + step).rem_euclid(len) as usize);
+    }
+}
+
+// The class each player has picked, keyed like `PlayerAssignments`.
+#[derive(Resource, Default)]
+pub struct PlayerClasses {
+    pub selected: HashMap<u32, CharacterClass>,
+}
+
+impl PlayerClasses {
+    pub fn class(&self, id: u32) -> CharacterClass {
+        self.selected.get(&id).copied().unwrap_or_default()
+    }
+
+    // Moves player `id` to the next (or previous) class, returning it.
+    pub fn cycle(&mut self, id: u32, step: isize) -> CharacterClass {
+        let all = CharacterClass::ALL;
+        let current = all.iter().position(|class| *class == self.class(id)).unwrap_or(0) as isize;
+        let class = all[(current + step).rem_euclid(all.len() as isize) as usize];
+        self.selected.insert(id, class);
+        class
+    }
+}
+
+// Players who haven't joined yet cycle through the palette with the bumpers
+// (or Tab on the keyboard) before confirming with the join button.
+fn select_player_color(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut assignments: ResMut<PlayerAssignments>,
+    mut colors: ResMut<PlayerColors>,
+    gamepads: Query<(Entity, &Gamepad)>,
+) {
+    for (entity, gamepad) in &gamepads {
+        let gid = assignments.gamepad_id(entity);
+        if assignments.players.contains_key(&gid) {
+            continue;
+        }
+        if gamepad.just_pressed(GamepadButton::RightTrigger) {
+            colors.cycle(gid, 1);
+        }
+        if gamepad.just_pressed(GamepadButton::LeftTrigger) {
+            colors.cycle(gid, -1);
+        }
+    }
+    if !assignments.players.contains_key(&KEYBOARD_ID) && keyboard_input.just_pressed(KeyCode::Tab) {
+        colors.cycle(KEYBOARD_ID, 1);
+    }
+}
+
+// Anyone can switch the game mode before the match starts, with M or the d-pad.
+fn select_game_mode(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut match_config: ResMut<MatchConfig>,
+    gamepads: Query<&Gamepad>,
+) {
+    let pressed = keyboard_input.just_pressed(KeyCode::KeyM)
+        || gamepads
+            .iter()
+            .any(|gamepad| gamepad.just_pressed(GamepadButton::DPadUp));
+    if pressed {
+        match_config.mode = match_config.mode.next();
+    }
+}
+
+// Players who haven't joined yet pick a class with the d-pad (or C on the keyboard)
+// before confirming with Start or the join button.
+fn select_player_class(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut assignments: ResMut<PlayerAssignments>,
+    mut classes: ResMut<PlayerClasses>,
+    mut announcements: EventWriter<Announcement>,
+    gamepads: Query<(Entity, &Gamepad)>,
+) {
+    let mut changes = Vec::new();
+    for (entity, gamepad) in &gamepads {
+        let gid = assignments.gamepad_id(entity);
+        if assignments.players.contains_key(&gid) {
+            continue;
+        }
+        if gamepad.just_pressed(GamepadButton::DPadRight) {
+            changes.push((gid, 1));
+        }
+        if gamepad.just_pressed(GamepadButton::DPadLeft) {
+            changes.push((gid, -1));
+        }
+    }
+    if !assignments.players.contains_key(&KEYBOARD_ID) && keyboard_input.just_pressed(KeyCode::KeyC) {
+        changes.push((KEYBOARD_ID, 1));
+    }
+    for (id, step) in changes {
+        let class = classes.cycle(id, step);
+        announcements.send(Announcement(format!("P{id}: {}", class.name())));
+    }
+}
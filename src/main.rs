@@ -6,39 +6,86 @@
 //! - Support for both keyboard and gamepad input
 //! - A configurable maximum slope angle for jumping
 //!
-//! The character controller logic is contained within the `plugin` module.
+//! The character controller logic is contained within the `player` module, and every
+//! character is spawned through `game::spawn_controllable_character` from a `SpawnConfig`.
 //!
 //! For a kinematic character controller, see the `kinematic_character_2d` example.
 
-use avian2d::{math::*, prelude::*};
+use avian2d::prelude::*;
 use bevy::prelude::*;
 
-mod game;
-mod input;
-mod player;
-mod weapons;
-
-use player::{
+use so_much_for_subtlety::player::{
     CharacterControllerPlugin,
     PlayerAssignments,
 };
 
-use game::setup;
+use so_much_for_subtlety::ai::AiPlugin;
+use so_much_for_subtlety::bindings::BindingsPlugin;
+use so_much_for_subtlety::camera::GameCameraPlugin;
+use so_much_for_subtlety::combat::CombatPlugin;
+use so_much_for_subtlety::debug::DebugPlugin;
+use so_much_for_subtlety::decals::DecalPlugin;
+use so_much_for_subtlety::game::{setup, MatchConfig, WorldBounds};
+use so_much_for_subtlety::grapple::GrapplePlugin;
+use so_much_for_subtlety::gravity::GravityPlugin;
+use so_much_for_subtlety::hazards::HazardsPlugin;
+use so_much_for_subtlety::hud::HudPlugin;
+use so_much_for_subtlety::lobby::LobbyPlugin;
+#[cfg(feature = "net")]
+use so_much_for_subtlety::net::NetPlugin;
+use so_much_for_subtlety::powerups::PowerupPlugin;
+use so_much_for_subtlety::replay::ReplayPlugin;
+use so_much_for_subtlety::rng::GameRng;
+use so_much_for_subtlety::round::RoundPlugin;
+use so_much_for_subtlety::rumble::RumblePlugin;
+use so_much_for_subtlety::scoring::ScoringPlugin;
+use so_much_for_subtlety::stress::StressTestPlugin;
+use so_much_for_subtlety::training::TrainingPlugin;
+use so_much_for_subtlety::trajectory::TrajectoryPlugin;
+use so_much_for_subtlety::triggers::TriggerPlugin;
 
 fn main() {
-    App::new()
+    let mut app = App::new();
+    app
         .add_plugins((
             DefaultPlugins,
             // Add physics plugins and specify a units-per-meter scaling factor, 1 meter = 20 pixels.
             // The unit allows the engine to tune its parameters for the scale of the world, improving stability.
             PhysicsPlugins::default().with_length_unit(20.0),
             CharacterControllerPlugin,
+            GravityPlugin,
+            HazardsPlugin,
+            GameCameraPlugin,
+            CombatPlugin,
+            RumblePlugin,
+            ScoringPlugin,
+            AiPlugin,
+        ))
+        // Plugin tuples are limited in size, so the rest go in a second batch
+        .add_plugins((
+            TrainingPlugin,
+            LobbyPlugin,
+            HudPlugin,
+            RoundPlugin,
+            DebugPlugin,
+            GrapplePlugin,
+            TrajectoryPlugin,
+            DecalPlugin,
+            ReplayPlugin,
+            TriggerPlugin,
+            BindingsPlugin,
+            StressTestPlugin,
+            PowerupPlugin,
         ))
         .insert_resource(ClearColor(Color::srgb(0.05, 0.05, 0.1)))
         .insert_resource(PlayerAssignments::default())
-        .insert_resource(Gravity(Vector::NEG_Y * 1000.0))
-        .add_systems(Startup, setup)
+        .insert_resource(WorldBounds::default())
+        .insert_resource(MatchConfig::default())
+        .insert_resource(GameRng::from_env())
+        .add_systems(Startup, setup);
         //.add_systems(Update, game::rotate_planet)
         //.add_systems(Update, gamepad_system)
-        .run();
+    #[cfg(feature = "net")]
+    app.add_plugins(NetPlugin);
+    app.run();
 }
@@ -0,0 +1,462 @@
+use avian2d::prelude::*;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::ErrorKind;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+use crate::combat::Damageable;
+use crate::game::{spawn_controllable_character, MatchConfig, SpawnConfig, SpawnPoints};
+use crate::hud::primary_player;
+use crate::input::{ActionWriter, InputBuffer};
+use crate::lobby::{PlayerClasses, PlayerColors};
+use crate::player::{CharacterController, PlayerAction, PlayerAssignments, PlayerId};
+use crate::replay::{ActionFrame, ReplayTick};
+use crate::snapshot::{PlayerSnapshot, PlayerSnapshots};
+
+// Playing over the network, with one instance as the authoritative server and the others
+// as clients. Clients send the `PlayerAction`s of their own player to the server, which
+// feeds them into its `PlayerAction` pipeline like any local input, and sends everyone's
+// `PlayerSnapshot` back every fixed update.
+//
+// Set `GAME_HOST` to a port to be the server, or `GAME_CONNECT` to the server's address
+// to be a client. Without either the plugin does nothing. Clients hold their own input
+// back by `GAME_INPUT_DELAY` ticks (see `InputBuffer`), `DEFAULT_INPUT_DELAY` if unset.
+pub struct NetPlugin;
+
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        let Some(role) = NetRole::from_env() else {
+            return;
+        };
+        let socket = match role.bind() {
+            Ok(socket) => socket,
+            Err(error) => {
+                error!("Couldn't open a socket to play over the network: {error}");
+                return;
+            }
+        };
+        info!("Playing over the network as {role:?}");
+        app.insert_resource(NetSocket(socket));
+        match role {
+            NetRole::Server { .. } => {
+                app.insert_resource(RemoteClients::default())
+                    .add_systems(PreUpdate, receive_on_server)
+                    .add_systems(FixedLast, broadcast_state);
+            }
+            NetRole::Client { server } => {
+                app.insert_resource(InputBuffer::new(input_delay()).forwarding())
+                    .insert_resource(ServerConnection::new(server))
+                    .add_systems(PreUpdate, receive_on_client)
+                    // Once the frame's input has all been sent
+                    .add_systems(PostUpdate, forward_actions);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum NetRole {
+    Server { port: u16 },
+    Client { server: SocketAddr },
+}
+
+impl NetRole {
+    // Reads the role from `GAME_HOST` or `GAME_CONNECT`, if either is set.
+    pub fn from_env() -> Option<Self> {
+        if let Ok(port) = std::env::var("GAME_HOST") {
+            match port.parse() {
+                Ok(port) => return Some(NetRole::Server { port }),
+                Err(error) => warn!("GAME_HOST isn't a port ({error}): {port}"),
+            }
+        }
+        if let Ok(address) = std::env::var("GAME_CONNECT") {
+            match address.to_socket_addrs().map(|mut addresses| addresses.next()) {
+                Ok(Some(server)) => return Some(NetRole::Client { server }),
+                _ => warn!("GAME_CONNECT isn't an address: {address}"),
+            }
+        }
+        None
+    }
+
+    fn bind(&self) -> std::io::Result<UdpSocket> {
+        let port = match self {
+            NetRole::Server { port } => *port,
+            // Any free port will do
+            NetRole::Client { .. } => 0,
+        };
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        // Systems drain whatever has arrived and move on
+        socket.set_nonblocking(true)?;
+        Ok(socket)
+    }
+}
+
+// How many ticks clients delay their own input by, unless `GAME_INPUT_DELAY` says otherwise.
+// About the round trip of a decent connection at 64 ticks per second.
+const DEFAULT_INPUT_DELAY: u32 = 3;
+
+fn input_delay() -> u32 {
+    std::env::var("GAME_INPUT_DELAY")
+        .ok()
+        .and_then(|delay| delay.parse().ok())
+        .unwrap_or(DEFAULT_INPUT_DELAY)
+}
+
+// Everything sent over the network. Each message goes in a packet of its own, packed
+// with bincode.
+#[derive(Serialize, Deserialize, Debug)]
+enum NetMessage {
+    // A client asking to play. Resent until the server welcomes it.
+    Join,
+    // The server's answer to `Join`: the client's character, as the server knows it.
+    Welcome { character: Entity },
+    // A client's actions for a frame, packed with `ActionFrame::pack`. Sent even when there
+    // are none to show it's still around. The server applies them to the client's
+    // character whatever entity they name.
+    Actions(Vec<u8>),
+    // Every character as the server sees it.
+    State(Vec<PlayerSnapshot>),
+}
+
+// The largest payload a UDP packet can carry.
+const MAX_PACKET_SIZE: usize = 65507;
+
+#[derive(Resource)]
+struct NetSocket(UdpSocket);
+
+impl NetSocket {
+    fn send(&self, message: &NetMessage, to: SocketAddr) {
+        let bytes = match bincode::serialize(message) {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                warn!("Couldn't encode {message:?}: {error}");
+                return;
+            }
+        };
+        if let Err(error) = self.0.send_to(&bytes, to) {
+            warn!("Couldn't send to {to}: {error}");
+        }
+    }
+
+    // Everything that has arrived since the last call.
+    fn receive(&self) -> Vec<(NetMessage, SocketAddr)> {
+        let mut buffer = vec![0; MAX_PACKET_SIZE];
+        let mut messages = Vec::new();
+        loop {
+            match self.0.recv_from(&mut buffer) {
+                Ok((length, from)) => match bincode::deserialize(&buffer[..length]) {
+                    Ok(message) => messages.push((message, from)),
+                    Err(error) => warn!("Dropping a malformed packet from {from}: {error}"),
+                },
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => {
+                    warn!("Couldn't receive: {error}");
+                    break;
+                }
+            }
+        }
+        messages
+    }
+}
+
+// Remote players get ids from here up, clear of the ones local gamepads are given.
+const REMOTE_ID_BASE: u32 = 100;
+// How long the server waits to hear from a client before dropping it, in seconds.
+const CLIENT_TIMEOUT_SECS: f32 = 5.0;
+
+struct RemoteClient {
+    player: u32,
+    // The client's tick as of the latest actions it sent. Packets can arrive out of order,
+    // and older actions than these are dropped.
+    tick: Option<u32>,
+    // When the client was last heard from, in seconds since startup.
+    last_heard: f32,
+}
+
+// The clients playing on this server, by address.
+#[derive(Resource, Default)]
+struct RemoteClients(HashMap<SocketAddr, RemoteClient>);
+
+impl RemoteClients {
+    fn next_player(&self) -> u32 {
+        let taken: HashSet<u32> = self.0.values().map(|client| client.player).collect();
+        (REMOTE_ID_BASE..).find(|id| !taken.contains(id)).unwrap()
+    }
+}
+
+fn receive_on_server(
+    mut commands: Commands,
+    time: Res<Time>,
+    socket: Res<NetSocket>,
+    mut clients: ResMut<RemoteClients>,
+    mut assignments: ResMut<PlayerAssignments>,
+    mut action_writer: ActionWriter,
+    spawn_points: Res<SpawnPoints>,
+    match_config: Res<MatchConfig>,
+    colors: Res<PlayerColors>,
+    classes: Res<PlayerClasses>,
+    characters: Query<&Transform, With<CharacterController>>,
+) {
+    let now = time.elapsed_secs();
+    for (message, from) in socket.receive() {
+        match message {
+            NetMessage::Join => {
+                if !clients.0.contains_key(&from) {
+                    let player = clients.next_player();
+                    info!("P{player} connected from {from}");
+                    clients.0.insert(
+                        from,
+                        RemoteClient {
+                            player,
+                            tick: None,
+                            last_heard: now,
+                        },
+                    );
+                }
+                let player = clients.0[&from].player;
+                // Joining again while playing just gets the same character back
+                let character = match assignments.players.get(&player) {
+                    Some(character) => *character,
+                    None if assignments.players.len() >= match_config.max_players => {
+                        warn!("P{player} can't join, the game is full");
+                        continue;
+                    }
+                    None => {
+                        let occupied: Vec<Vec2> = characters
+                            .iter()
+                            .map(|transform| transform.translation.truncate())
+                            .collect();
+                        let character = spawn_controllable_character(
+                            &mut commands,
+                            &SpawnConfig {
+                                position: spawn_points.pick(&occupied),
+                                color: colors.color(player),
+                                invulnerability_secs: match_config.invulnerability_secs,
+                                ..classes.class(player).spawn_config()
+                            },
+                        );
+                        commands.entity(character).insert(PlayerId(player));
+                        assignments.players.insert(player, character);
+                        character
+                    }
+                };
+                socket.send(&NetMessage::Welcome { character }, from);
+            }
+            NetMessage::Actions(bytes) => {
+                let Some(client) = clients.0.get_mut(&from) else {
+                    continue;
+                };
+                let frame = match ActionFrame::unpack(&bytes) {
+                    Ok(frame) => frame,
+                    Err(error) => {
+                        warn!("Dropping malformed actions from {from}: {error}");
+                        continue;
+                    }
+                };
+                client.last_heard = now;
+                // Ticks wrap around, so compare how far apart they are
+                if let Some(tick) = client.tick {
+                    if (frame.tick.wrapping_sub(tick) as i32) < 0 {
+                        continue;
+                    }
+                }
+                client.tick = Some(frame.tick);
+                let Some(character) = assignments.players.get(&client.player) else {
+                    continue;
+                };
+                for action in frame.actions {
+                    action_writer.send(action.for_character(*character));
+                }
+            }
+            // Only the server sends these
+            NetMessage::Welcome { .. } | NetMessage::State(_) => {}
+        }
+    }
+    // Take the characters of clients that went quiet out of the game
+    clients.0.retain(|address, client| {
+        if now - client.last_heard <= CLIENT_TIMEOUT_SECS {
+            return true;
+        }
+        info!("P{} at {address} timed out", client.player);
+        if let Some(character) = assignments.players.remove(&client.player) {
+            commands.entity(character).despawn_recursive();
+        }
+        false
+    });
+}
+
+fn broadcast_state(
+    socket: Res<NetSocket>,
+    clients: Res<RemoteClients>,
+    snapshots: PlayerSnapshots,
+) {
+    if clients.0.is_empty() {
+        return;
+    }
+    let message = NetMessage::State(snapshots.all());
+    for address in clients.0.keys() {
+        socket.send(&message, *address);
+    }
+}
+
+// How far the local player can drift from where the server has them before being moved
+// straight there, rather than eased over.
+const CORRECTION_SNAP_DISTANCE: f32 = 64.0;
+// How much of the way to the server's position the local player is moved per update.
+const CORRECTION_RATE: f32 = 0.2;
+
+// A client's view of the server. The local player plays their own character, which is
+// kept in line with the server's, and everyone else shows up as a mirror of theirs.
+#[derive(Resource)]
+struct ServerConnection {
+    server: SocketAddr,
+    // The local player's character here, and the same character on the server.
+    local: Option<Entity>,
+    remote: Option<Entity>,
+    // Stand-ins for the server's other characters, keyed by the server's entity.
+    mirrors: HashMap<Entity, Entity>,
+}
+
+impl ServerConnection {
+    fn new(server: SocketAddr) -> Self {
+        Self {
+            server,
+            local: None,
+            remote: None,
+            mirrors: HashMap::new(),
+        }
+    }
+}
+
+// A character standing in for one of the server's, moved to wherever the server says.
+#[derive(Component)]
+struct Mirror;
+
+fn forward_actions(
+    socket: Res<NetSocket>,
+    tick: Res<ReplayTick>,
+    mut connection: ResMut<ServerConnection>,
+    assignments: Res<PlayerAssignments>,
+    mut buffer: ResMut<InputBuffer>,
+) {
+    // Input is passed on as it's made, not once it's applied here
+    let made = buffer.take_outgoing();
+    let local = primary_player(&assignments);
+    if local != connection.local {
+        // A new character, e.g. after respawning, has to join all over again
+        connection.local = local;
+        connection.remote = None;
+    }
+    let Some(local) = local else {
+        return;
+    };
+    if connection.remote.is_none() {
+        socket.send(&NetMessage::Join, connection.server);
+    }
+    let actions = made
+        .into_iter()
+        .filter(|action| action.character() == local)
+        .collect();
+    let frame = ActionFrame {
+        tick: tick.0,
+        actions,
+    };
+    match frame.pack() {
+        Ok(bytes) => socket.send(&NetMessage::Actions(bytes), connection.server),
+        Err(error) => warn!("Couldn't pack {frame:?}: {error}"),
+    }
+}
+
+fn receive_on_client(
+    mut commands: Commands,
+    socket: Res<NetSocket>,
+    mut connection: ResMut<ServerConnection>,
+    colors: Res<PlayerColors>,
+    mut characters: Query<(&mut Position, &mut LinearVelocity, Option<&mut Damageable>)>,
+) {
+    // Only the latest state matters
+    let mut latest = None;
+    // Whether the latest state is older than the welcome, and may not have the character yet
+    let mut welcomed_since = false;
+    for (message, _) in socket.receive() {
+        match message {
+            NetMessage::Welcome { character } => {
+                connection.remote = Some(character);
+                welcomed_since = true;
+                // It may have been showing up as someone else's until now
+                if let Some(mirror) = connection.mirrors.remove(&character) {
+                    if let Some(mut entity) = commands.get_entity(mirror) {
+                        entity.despawn_recursive();
+                    }
+                }
+            }
+            NetMessage::State(snapshots) => {
+                latest = Some(snapshots);
+                welcomed_since = false;
+            }
+            // Only clients send these
+            NetMessage::Join | NetMessage::Actions(_) => {}
+        }
+    }
+    let Some(snapshots) = latest else {
+        return;
+    };
+    // The server takes characters that die out of the game, so once the local player's is
+    // gone from the state they have to join again for a new one
+    if let Some(remote) = connection.remote {
+        if !welcomed_since && !snapshots.iter().any(|snapshot| snapshot.entity == remote) {
+            info!("Our character is gone from the server, joining again");
+            connection.remote = None;
+        }
+    }
+    let mut seen = HashSet::new();
+    for snapshot in snapshots {
+        let local = connection
+            .local
+            .filter(|_| connection.remote == Some(snapshot.entity));
+        let existing = local.or_else(|| connection.mirrors.get(&snapshot.entity).copied());
+        let character = match existing {
+            Some(character) if characters.contains(character) => character,
+            // Not seen before, or gone here, so it needs a new mirror
+            _ => {
+                let mirror = spawn_controllable_character(
+                    &mut commands,
+                    &SpawnConfig {
+                        position: snapshot.position,
+                        color: colors.color(snapshot.player.unwrap_or_default()),
+                        ..default()
+                    },
+                );
+                commands.entity(mirror).insert(Mirror);
+                connection.mirrors.insert(snapshot.entity, mirror);
+                seen.insert(snapshot.entity);
+                continue;
+            }
+        };
+        seen.insert(snapshot.entity);
+        let Ok((mut position, mut velocity, damageable)) = characters.get_mut(character) else {
+            continue;
+        };
+        if local.is_some() && position.0.distance(snapshot.position) < CORRECTION_SNAP_DISTANCE {
+            // The local player moves ahead on their own input, so only nudge them back
+            position.0 = position.0.lerp(snapshot.position, CORRECTION_RATE);
+        } else {
+            position.0 = snapshot.position;
+            velocity.0 = snapshot.velocity;
+        }
+        if let Some(mut damageable) = damageable {
+            damageable.health = snapshot.health;
+        }
+    }
+    // Characters the server no longer has
+    connection.mirrors.retain(|remote, mirror| {
+        let keep = seen.contains(remote);
+        if !keep {
+            if let Some(mut entity) = commands.get_entity(*mirror) {
+                entity.despawn_recursive();
+            }
+        }
+        keep
+    });
+}
@@ -1,46 +1,288 @@
 use avian2d::{math::*, prelude::*};
 use bevy::{ecs::query::Has, prelude::*};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 pub struct CharacterControllerPlugin;
-use crate::input::{gamepad_input, keyboard_input};
-use crate::weapons::{Gun, Projectile};
-use crate::game::{spawn_character, move_objects};
+use crate::gravity::LocalGravity;
+use crate::input::{
+    gamepad_input, keyboard_input, release_delayed_actions, sample_input, InputBuffer,
+};
+use crate::bindings::not_remapping;
+use crate::combat::{CombatSet, GunBash};
+use crate::replay::not_replaying;
+use crate::rng::GameRng;
+use crate::weapons::{
+    cool_guns, enforce_projectile_budget, recover_spread, reset_projectile, split_projectiles,
+    AimConstraint, Gun, GunVisual, Heat, Loadout, ProjectileBudget, SelfImpulse, Shot,
+    ShotFired, Spread, Sticky, Weapon, WeaponSlot,
+};
+use crate::game::{
+    apply_movement_mode,
+    dress_characters,
+    CharacterColor,
+    enforce_world_bounds,
+    move_objects,
+    spawn_character,
+    MatchConfig,
+};
 
 impl Plugin for CharacterControllerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<PlayerAction>().add_systems(
-            Update,
-            (
-                keyboard_input,
-                gamepad_input,
-                update_grounded,
-                apply_movement_damping,
-                apply_aim_to_gun,
-                move_objects,
-                spawn_character,
-                movement,
+        app.add_event::<PlayerAction>()
+            .add_event::<ShotFired>()
+            .insert_resource(AimAssist::default())
+            .init_resource::<ProjectileBudget>()
+            .init_resource::<InputBuffer>()
+            .add_systems(
+                Update,
+                (
+                    keyboard_input.run_if(not_replaying),
+                    gamepad_input.run_if(not_replaying),
+                    spawn_character.run_if(not_remapping),
+                    dress_characters,
+                    apply_movement_mode,
+                )
+                    .chain(),
             )
-                .chain(),
-        );
+            // Actions go out on fixed updates, so they're applied there, along with everything
+            // else that moves characters and projectiles or draws from the `GameRng`. With the
+            // fixed time step it all plays out the same whatever the frame rate, which replays
+            // rely on.
+            .add_systems(
+                FixedUpdate,
+                (
+                    update_grounded,
+                    movement,
+                    apply_jump_gravity,
+                    glide,
+                    snap_to_ground,
+                    slide_off_steep_slopes,
+                    carry_platform_riders,
+                    apply_movement_damping,
+                    // System tuples are limited in size, so the gun systems are grouped
+                    (
+                        recover_spread,
+                        cool_guns,
+                        smooth_aim,
+                        apply_aim_to_gun,
+                        split_projectiles,
+                        enforce_projectile_budget,
+                    )
+                        .chain(),
+                    move_objects,
+                    enforce_world_bounds,
+                )
+                    .chain()
+                    .before(CombatSet::Damage),
+            )
+            // Live input is sampled once a frame, and goes out at the start of the tick it's due on
+            .add_systems(PreUpdate, sample_input)
+            .add_systems(FixedPreUpdate, release_delayed_actions)
+            // Gravity is applied during the physics step, so the fall speed is clamped right after it
+            .add_systems(
+                FixedPostUpdate,
+                clamp_fall_speed.after(PhysicsSet::StepSimulation),
+            );
     }
 }
 
 // An event sent for a movement input action.
-#[derive(Event)]
+#[derive(Event, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "net", derive(serde::Serialize, serde::Deserialize))]
 pub enum PlayerAction {
     Move(Entity, Scalar),
     Jump(Entity),
     Aim(Entity, Scalar, Scalar),
-    Fire(Entity),
+    // Pulls the trigger for a gun slot with a pressure from 0 to 1. Digital buttons always
+    // send 1.
+    Fire(Entity, WeaponSlot, Scalar),
+    Stomp(Entity),
+    // Shoots a grappling hook along the aim, or lets go of it.
+    Grapple(Entity),
+    ReleaseGrapple(Entity),
+    // Sent every frame jump is held, to glide while falling.
+    Glide(Entity),
+    // Shows or hides the predicted path of the next shot.
+    TogglePreview(Entity),
+    // Sent every frame the walk modifier is held, to move slower for precise platforming.
+    Walk(Entity),
+    // Sets off every mine the character has laid.
+    Detonate(Entity),
+}
+
+impl PlayerAction {
+    // The character the action is for.
+    pub fn character(&self) -> Entity {
+        match self {
+            PlayerAction::Move(entity, _)
+            | PlayerAction::Jump(entity)
+            | PlayerAction::Aim(entity, _, _)
+            | PlayerAction::Fire(entity, _, _)
+            | PlayerAction::Stomp(entity)
+            | PlayerAction::Grapple(entity)
+            | PlayerAction::ReleaseGrapple(entity)
+            | PlayerAction::Glide(entity)
+            | PlayerAction::TogglePreview(entity)
+            | PlayerAction::Walk(entity)
+            | PlayerAction::Detonate(entity) => *entity,
+        }
+    }
+
+    // The same action, for another character.
+    pub fn for_character(mut self, character: Entity) -> Self {
+        match &mut self {
+            PlayerAction::Move(entity, _)
+            | PlayerAction::Jump(entity)
+            | PlayerAction::Aim(entity, _, _)
+            | PlayerAction::Fire(entity, _, _)
+            | PlayerAction::Stomp(entity)
+            | PlayerAction::Grapple(entity)
+            | PlayerAction::ReleaseGrapple(entity)
+            | PlayerAction::Glide(entity)
+            | PlayerAction::TogglePreview(entity)
+            | PlayerAction::Walk(entity)
+            | PlayerAction::Detonate(entity) => *entity = character,
+        }
+        self
+    }
+
+    // Whether the action is sent for as long as an input is held, rather than once when
+    // it's pressed.
+    pub fn is_held(&self) -> bool {
+        matches!(
+            self,
+            PlayerAction::Move(..)
+                | PlayerAction::Aim(..)
+                | PlayerAction::Fire(..)
+                | PlayerAction::Glide(_)
+                | PlayerAction::Walk(_)
+        )
+    }
+
+    // Whether this is the same input as `other` for the same character, whatever the
+    // values it carries. Each gun slot's trigger counts as an input of its own.
+    pub fn replaces(&self, other: &PlayerAction) -> bool {
+        if self.character() != other.character() {
+            return false;
+        }
+        match (self, other) {
+            (PlayerAction::Fire(_, slot, _), PlayerAction::Fire(_, other_slot, _)) => {
+                slot == other_slot
+            }
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+// Gently bends analog aim towards the nearest character within a cone around it.
+// The aim is only ever nudged by `strength` (0 to 1) of the remaining angle,
+// so the player always stays in control.
+#[derive(Resource)]
+pub struct AimAssist {
+    pub enabled: bool,
+    pub cone_radians: Scalar,
+    pub strength: Scalar,
+}
+
+impl Default for AimAssist {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            cone_radians: (15.0 as Scalar).to_radians(),
+            strength: 0.35,
+        }
+    }
 }
 
+impl AimAssist {
+    // Returns the assisted aim direction for a character at `origin` aiming along `direction`.
+    pub fn assist(
+        &self,
+        origin: Vector,
+        direction: Vector,
+        targets: impl Iterator<Item = Vector>,
+    ) -> Vector {
+        if !self.enabled || direction == Vector::ZERO {
+            return direction;
+        }
+        let nearest = targets
+            .map(|target| target - origin)
+            .filter(|offset| direction.angle_to(*offset).abs() <= self.cone_radians)
+            .min_by(|a, b| a.length_squared().total_cmp(&b.length_squared()));
+        match nearest {
+            Some(offset) => {
+                let correction = direction.angle_to(offset) * self.strength;
+                Vector::from_angle(correction).rotate(direction)
+            }
+            None => direction,
+        }
+    }
+}
+
+// The player id of whoever plays on the keyboard.
+pub const KEYBOARD_ID: u32 = 0;
+
 #[derive(Resource, Default)]
 pub struct PlayerAssignments {
-    // Map each Gamepad to its spawned character
+    // Map each player id to its spawned character
     pub players: HashMap<u32, Entity>,
+    // Player ids handed out to gamepads. Ids are given out in order as gamepads
+    // show up, rather than derived from the gamepad entity, so that they stay unique.
+    pub gamepads: HashMap<Entity, u32>,
 }
 
+impl PlayerAssignments {
+    // The player id of `device`. The keyboard always has `KEYBOARD_ID`.
+    pub fn device_id(&mut self, device: InputDevice) -> u32 {
+        match device {
+            InputDevice::Keyboard => KEYBOARD_ID,
+            InputDevice::Gamepad(gamepad) => self.gamepad_id(gamepad),
+        }
+    }
+
+    // The device player `id` plays with, if any.
+    pub fn device_for(&self, id: u32) -> Option<InputDevice> {
+        if id == KEYBOARD_ID {
+            return Some(InputDevice::Keyboard);
+        }
+        self.gamepad_for(id).map(InputDevice::Gamepad)
+    }
+
+    // The player id of `gamepad`, handing out a new one if it hasn't been seen before.
+    pub fn gamepad_id(&mut self, gamepad: Entity) -> u32 {
+        let next = KEYBOARD_ID + 1 + self.gamepads.len() as u32;
+        *self.gamepads.entry(gamepad).or_insert(next)
+    }
+
+    // The gamepad player `id` plays with, if any.
+    pub fn gamepad_for(&self, id: u32) -> Option<Entity> {
+        self.gamepads
+            .iter()
+            .find(|(_, gamepad_id)| **gamepad_id == id)
+            .map(|(gamepad, _)| *gamepad)
+    }
+
+    // Finds which player controls the given character.
+    pub fn player_id(&self, character: Entity) -> Option<u32> {
+        self.players
+            .iter()
+            .find(|(_, entity)| **entity == character)
+            .map(|(id, _)| *id)
+    }
+}
+
+// The device a player's character is controlled with.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InputDevice {
+    Keyboard,
+    Gamepad(Entity),
+}
+
+// The id of the player controlling a character, as used in `PlayerAssignments`.
+// Unlike the character entity it stays the same across respawns.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PlayerId(pub u32);
+
 // A marker component indicating that an entity is using a character controller.
 #[derive(Component)]
 pub struct CharacterController;
@@ -50,29 +292,313 @@ pub struct CharacterController;
 #[component(storage = "SparseSet")]
 pub struct Grounded;
 // The acceleration used for character movement.
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct MovementAcceleration(Scalar);
 
 // The damping factor used for slowing down movement.
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct MovementDampingFactor(Scalar);
 
 // The strength of a jump.
-#[derive(Component)]
+#[derive(Component, Clone, Debug, PartialEq)]
 pub struct JumpImpulse(Scalar);
 
-#[derive(Component)]
-pub struct FireImpulse(Scalar);
+// How hard each trigger is pulled this frame, from 0 (released) to 1 (fully pressed),
+// indexed by `WeaponSlot::index`.
+#[derive(Component, Clone)]
+pub struct FireImpulse(pub [Scalar; 2]);
+
+impl FireImpulse {
+    pub fn get(&self, slot: WeaponSlot) -> Scalar {
+        self.0[slot.index()]
+    }
+
+    pub fn press(&mut self, slot: WeaponSlot, pressure: Scalar) {
+        let trigger = &mut self.0[slot.index()];
+        *trigger = trigger.max(pressure.clamp(0.0, 1.0));
+    }
+
+    pub fn release(&mut self) {
+        self.0 = [0.0; 2];
+    }
+}
 
 // The maximum angle a slope can have for a character controller
 // to be able to climb and jump. If the slope is steeper than this angle,
 // the character will slide down.
 
+#[derive(Component, Clone)]
+pub struct AimRotation(pub Quat);
+
+impl AimRotation {
+    // The direction being aimed in. Guns point down their local Y axis.
+    pub fn direction(&self) -> Vector {
+        (self.0 * Vec3::NEG_Y).truncate()
+    }
+}
+
+// The rotation the gun actually points in, easing towards `AimRotation` at `rate` so
+// that noisy stick input doesn't make it jitter. Shots leave along this rotation too, so
+// they go where the gun is seen pointing. A `rate` of zero turns smoothing off.
+#[derive(Component, Clone)]
+pub struct SmoothedAim {
+    pub rotation: Quat,
+    // How quickly the gun catches up with the aim, per second.
+    pub rate: Scalar,
+}
+
+impl Default for SmoothedAim {
+    fn default() -> Self {
+        Self {
+            rotation: Quat::IDENTITY,
+            rate: 0.0,
+        }
+    }
+}
+
+// How a character gets around. In space there is no ground to stand on or gravity
+// to fall with: moving thrusts along the aim and jumping boosts.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MovementMode {
+    #[default]
+    Platformer,
+    Space,
+}
+
+#[derive(Component, Clone)]
+pub struct MaxSlopeAngle(Scalar);
+
+// The fastest a character can run along the ground or through the air. Faster movement,
+// e.g. from knockback, isn't slowed down, but moving can't add to it.
+#[derive(Component, Clone)]
+pub struct MaxSpeed(pub Scalar);
+
+impl Default for MaxSpeed {
+    fn default() -> Self {
+        Self(400.0)
+    }
+}
+
+// How running acceleration changes with speed. The curve maps how close the character
+// is to its `MaxSpeed` (0 standing still, 1 at top speed) to a multiplier on
+// `MovementAcceleration`.
+#[derive(Component, Clone, Copy, Default)]
+pub enum AccelCurve {
+    // The same acceleration all the way up to top speed.
+    #[default]
+    Linear,
+    // Twice the acceleration from a standstill, tapering off to a quarter of it near top
+    // speed, for quick starts that still settle into a controlled run.
+    EaseOut,
+    Custom(fn(Scalar) -> Scalar),
+}
+
+impl AccelCurve {
+    pub fn multiplier(&self, speed_fraction: Scalar) -> Scalar {
+        let t = speed_fraction.clamp(0.0, 1.0);
+        let multiplier = match self {
+            AccelCurve::Linear => 1.0,
+            AccelCurve::EaseOut => 0.25 + 1.75 * (1.0 - t) * (1.0 - t),
+            AccelCurve::Custom(curve) => curve(t),
+        };
+        // A negative multiplier would turn running into braking
+        multiplier.max(0.0)
+    }
+}
+
+// Extra braking while running against the way the character is moving, so that turning
+// around is crisp rather than waiting on damping. `strength` is the share of the opposing
+// speed taken out per second, so anything past the frame rate stops it outright.
+#[derive(Component, Clone)]
+pub struct TurnBoost {
+    pub strength: Scalar,
+}
+
+impl Default for TurnBoost {
+    fn default() -> Self {
+        Self { strength: 12.0 }
+    }
+}
+
+// How `MovementAcceleration` and `MaxSpeed` are scaled while walking and running.
+// Characters run unless the walk modifier is held.
+#[derive(Component, Clone)]
+pub struct Gait {
+    pub walk_multiplier: Scalar,
+    pub run_multiplier: Scalar,
+}
+
+impl Default for Gait {
+    fn default() -> Self {
+        Self {
+            walk_multiplier: 0.4,
+            run_multiplier: 1.0,
+        }
+    }
+}
+
+// The maximum speed a character can fall at.
+#[derive(Component, Clone)]
+pub struct TerminalVelocity(Scalar);
+
+// A marker component for characters that get carried along by the
+// kinematic platform they stand on, whether it moves or rotates like a turntable.
 #[derive(Component)]
-pub struct AimRotation(Quat);
+#[require(PlatformVelocity, StandingOn)]
+pub struct MovingPlatformRider;
 
+// A ground-pound that slams an airborne character down at `velocity`,
+// damaging everything within `radius` of where it lands.
 #[derive(Component)]
-pub struct MaxSlopeAngle(Scalar);
+pub struct Stomp {
+    pub velocity: Scalar,
+    pub damage: f32,
+    pub radius: Scalar,
+}
+
+impl Default for Stomp {
+    fn default() -> Self {
+        Self {
+            velocity: 1500.0,
+            damage: 30.0,
+            radius: 120.0,
+        }
+    }
+}
+
+// A marker component indicating that a character is in the middle of a stomp.
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub struct Stomping;
+
+// Lets a character glide by holding jump while falling, scaling down its gravity
+// and terminal velocity by `gravity_scale`.
+#[derive(Component, Clone)]
+pub struct Glide {
+    pub gravity_scale: Scalar,
+}
+
+impl Default for Glide {
+    fn default() -> Self {
+        Self { gravity_scale: 0.25 }
+    }
+}
+
+// Changes how strongly gravity pulls on a character over the course of a jump: a bit
+// lighter at the apex for hang time, and heavier on the way down for a snappy landing.
+// "Up" follows the character's local gravity, so jumps feel the same all around a planet.
+#[derive(Component, Clone, Copy)]
+pub struct JumpGravity {
+    // Gravity scale while moving up faster than `apex_speed`, and while on the ground.
+    pub rising_scale: Scalar,
+    // Gravity scale while moving down faster than `apex_speed`.
+    pub falling_scale: Scalar,
+    // Gravity scale while the vertical speed is within `apex_speed` of zero.
+    pub apex_scale: Scalar,
+    pub apex_speed: Scalar,
+}
+
+impl Default for JumpGravity {
+    fn default() -> Self {
+        Self {
+            rising_scale: 1.0,
+            falling_scale: 1.6,
+            apex_scale: 0.5,
+            apex_speed: 80.0,
+        }
+    }
+}
+
+impl JumpGravity {
+    // The gravity scale for a character moving at `vertical_speed` against gravity.
+    pub fn scale(&self, vertical_speed: Scalar, grounded: bool) -> Scalar {
+        if grounded || vertical_speed > self.apex_speed {
+            self.rising_scale
+        } else if vertical_speed < -self.apex_speed {
+            self.falling_scale
+        } else {
+            self.apex_scale
+        }
+    }
+}
+
+// A marker component indicating that a character is gliding, remembering the
+// gravity scale to go back to once it stops.
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub struct Gliding {
+    previous_gravity_scale: Scalar,
+}
+
+// The normal of the ground below a character, pointing away from the ground.
+// Points straight up while airborne.
+#[derive(Component)]
+pub struct GroundNormal(pub Vector);
+
+impl Default for GroundNormal {
+    fn default() -> Self {
+        Self(Vector::Y)
+    }
+}
+
+// How quickly a character slides down ground steeper than its `MaxSlopeAngle`.
+#[derive(Component)]
+pub struct SlideAcceleration(pub Scalar);
+
+impl Default for SlideAcceleration {
+    fn default() -> Self {
+        Self(1200.0)
+    }
+}
+
+// The body a character is currently standing on, if any.
+#[derive(Component, Default)]
+pub struct StandingOn(pub Option<Entity>);
+
+// Ground that changes how characters standing on it move, e.g. sticky mud or slippery ice.
+// `damping_mul` scales how much speed is shed each frame and `accel_mul` scales acceleration.
+#[derive(Component, Clone, Copy)]
+pub struct TerrainModifier {
+    pub damping_mul: Scalar,
+    pub accel_mul: Scalar,
+}
+
+impl TerrainModifier {
+    // The terrain a character is standing on, if it has a modifier.
+    fn under<'a>(
+        standing_on: Option<&StandingOn>,
+        terrain: &'a Query<&TerrainModifier>,
+    ) -> Option<&'a TerrainModifier> {
+        standing_on
+            .and_then(|standing_on| standing_on.0)
+            .and_then(|ground| terrain.get(ground).ok())
+    }
+}
+
+// Keeps a character on the ground when it runs over the top of a slope, instead of
+// launching off it. Ground up to `max_distance` below the character pulls it down, as long
+// as the character isn't moving away from it. Only ground the ground caster can reach
+// counts, so distances past its max distance have no effect.
+#[derive(Component, Clone)]
+pub struct GroundSnap {
+    pub max_distance: Scalar,
+}
+
+impl Default for GroundSnap {
+    fn default() -> Self {
+        Self { max_distance: 8.0 }
+    }
+}
+
+// How far above the ground a snapped character is left, so it doesn't end up inside it.
+const GROUND_SNAP_SKIN: Scalar = 0.5;
+// How fast a character can be moving away from the ground and still be snapped to it.
+// Anything faster, like a jump, gets to leave the ground.
+const GROUND_SNAP_MAX_RISE: Scalar = 60.0;
+
+// The surface velocity a rider has inherited from the ground it stands on.
+#[derive(Component, Default)]
+pub struct PlatformVelocity(Vector);
 
 // A bundle that contains the components needed for a basic
 // kinematic character controller.
@@ -82,44 +608,123 @@ pub struct CharacterControllerBundle {
     rigid_body: RigidBody,
     collider: Collider,
     ground_caster: ShapeCaster,
+    ground_normal: GroundNormal,
+    movement_mode: MovementMode,
+    local_gravity: LocalGravity,
+    slide_acceleration: SlideAcceleration,
+    max_speed: MaxSpeed,
+    gait: Gait,
     locked_axes: LockedAxes,
     movement: MovementBundle,
 }
 
 // A bundle that contains components for character movement.
-#[derive(Bundle)]
+#[derive(Bundle, Clone)]
 pub struct MovementBundle {
     acceleration: MovementAcceleration,
     damping: MovementDampingFactor,
     jump_impulse: JumpImpulse,
     aiming: AimRotation,
+    smoothed_aim: SmoothedAim,
     max_slope_angle: MaxSlopeAngle,
     fire_impulse: FireImpulse,
+    terminal_velocity: TerminalVelocity,
+    jump_gravity: JumpGravity,
+    accel_curve: AccelCurve,
+}
+
+// Clamps a movement parameter into `min..=max`, warning when it was outside, so that
+// mis-tuned numbers show up in the log instead of as odd movement.
+fn clamp_movement_param(name: &str, value: Scalar, min: Scalar, max: Scalar) -> Scalar {
+    if value.is_nan() {
+        warn!("Movement parameter {name} is NaN, using {min}");
+        return min;
+    }
+    let clamped = value.clamp(min, max);
+    if clamped != value {
+        warn!("Movement parameter {name} {value} is out of range, using {clamped}");
+    }
+    clamped
 }
 
+// Movement is tuned with the builder methods below, starting from the default, e.g.
+// `MovementBundle::default().acceleration(1250.0).damping(0.92).jump_impulse(800.0)`.
+// Damping is clamped to 0..=1, since a larger factor would speed characters up instead,
+// and accelerations, impulses and speeds can't be negative.
 impl MovementBundle {
-    pub const fn new(
-        acceleration: Scalar,
-        damping: Scalar,
-        jump_impulse: Scalar,
-        aiming: Quat,
-        max_slope_angle: Scalar,
-        fire_impulse: Scalar,
-    ) -> Self {
-        Self {
-            acceleration: MovementAcceleration(acceleration),
-            damping: MovementDampingFactor(damping),
-            jump_impulse: JumpImpulse(jump_impulse),
-            aiming: AimRotation(aiming),
-            max_slope_angle: MaxSlopeAngle(max_slope_angle),
-            fire_impulse: FireImpulse(fire_impulse),
-        }
+    pub fn acceleration(mut self, acceleration: Scalar) -> Self {
+        let acceleration = clamp_movement_param("acceleration", acceleration, 0.0, Scalar::MAX);
+        self.acceleration = MovementAcceleration(acceleration);
+        self
+    }
+
+    pub fn damping(mut self, damping: Scalar) -> Self {
+        self.damping = MovementDampingFactor(clamp_movement_param("damping", damping, 0.0, 1.0));
+        self
+    }
+
+    pub fn jump_impulse(mut self, jump_impulse: Scalar) -> Self {
+        let jump_impulse = clamp_movement_param("jump impulse", jump_impulse, 0.0, Scalar::MAX);
+        self.jump_impulse = JumpImpulse(jump_impulse);
+        self
+    }
+
+    pub fn aiming(mut self, aiming: Quat) -> Self {
+        self.aiming = AimRotation(aiming);
+        self.smoothed_aim.rotation = aiming;
+        self
+    }
+
+    // How quickly the gun follows the aim, per second. Zero snaps it straight there.
+    pub fn aim_smoothing(mut self, rate: Scalar) -> Self {
+        self.smoothed_aim.rate = clamp_movement_param("aim smoothing", rate, 0.0, Scalar::MAX);
+        self
+    }
+
+    // The steepest slope the character can stand on, in degrees.
+    pub fn max_slope_deg(mut self, degrees: Scalar) -> Self {
+        let angle = clamp_movement_param("max slope angle", degrees.to_radians(), 0.0, PI / 2.0);
+        self.max_slope_angle = MaxSlopeAngle(angle);
+        self
+    }
+
+    pub fn fire_impulse(mut self, fire_impulse: Scalar) -> Self {
+        self.fire_impulse = FireImpulse([fire_impulse; 2]);
+        self
+    }
+
+    pub fn terminal_velocity(mut self, terminal_velocity: Scalar) -> Self {
+        let terminal_velocity =
+            clamp_movement_param("terminal velocity", terminal_velocity, 0.0, Scalar::MAX);
+        self.terminal_velocity = TerminalVelocity(terminal_velocity);
+        self
+    }
+
+    pub fn jump_gravity(mut self, jump_gravity: JumpGravity) -> Self {
+        self.jump_gravity = jump_gravity;
+        self
+    }
+
+    pub fn accel_curve(mut self, accel_curve: AccelCurve) -> Self {
+        self.accel_curve = accel_curve;
+        self
     }
 }
 
 impl Default for MovementBundle {
     fn default() -> Self {
-        Self::new(30.0, 0.1, 200.0, Quat::IDENTITY, PI * 0.45, 0.0)
+        Self {
+            acceleration: MovementAcceleration(30.0),
+            damping: MovementDampingFactor(0.1),
+            jump_impulse: JumpImpulse(200.0),
+            aiming: AimRotation(Quat::IDENTITY),
+            smoothed_aim: SmoothedAim::default(),
+            max_slope_angle: MaxSlopeAngle(PI * 0.45),
+            fire_impulse: FireImpulse([0.0; 2]),
+            terminal_velocity: TerminalVelocity(2000.0),
+            jump_gravity: JumpGravity::default(),
+            accel_curve: AccelCurve::default(),
+        }
     }
 }
 
@@ -135,33 +740,31 @@ impl CharacterControllerBundle {
             collider,
             ground_caster: ShapeCaster::new(caster_shape, Vector::ZERO, 0.0, Dir2::NEG_Y)
                 .with_max_distance(10.0),
+            ground_normal: GroundNormal::default(),
+            movement_mode: MovementMode::default(),
+            local_gravity: LocalGravity::default(),
+            slide_acceleration: SlideAcceleration::default(),
+            max_speed: MaxSpeed::default(),
+            gait: Gait::default(),
             locked_axes: LockedAxes::ROTATION_LOCKED,
             movement: MovementBundle::default(),
         }
     }
 
-    pub fn with_movement(
-        mut self,
-        acceleration: Scalar,
-        damping: Scalar,
-        jump_impulse: Scalar,
-        aiming: Quat,
-        max_slope_angle: Scalar,
-        fire_impulse: Scalar,
-    ) -> Self {
-        self.movement = MovementBundle::new(
-            acceleration,
-            damping,
-            jump_impulse,
-            aiming,
-            max_slope_angle,
-            fire_impulse,
-        );
+    pub fn with_movement(mut self, movement: MovementBundle) -> Self {
+        self.movement = movement;
+        self
+    }
+
+    pub fn with_max_speed(mut self, max_speed: Scalar, gait: Gait) -> Self {
+        self.max_speed = MaxSpeed(max_speed);
+        self.gait = gait;
         self
     }
 }
 
 fn movement(
+  mut commands: Commands,
   time: Res<Time>,
   mut movement_event_reader: EventReader<PlayerAction>,
   mut controllers: Query<(
@@ -173,15 +776,95 @@ fn movement(
       Has<Grounded>,
       &mut FireImpulse,
   )>,
+  positions: Query<(Entity, &Transform), With<CharacterController>>,
+  aim_assist: Res<AimAssist>,
+  stomps: Query<&Stomp>,
+  standing_on: Query<&StandingOn>,
+  terrain: Query<&TerrainModifier>,
+  ground_normals: Query<&GroundNormal>,
+  local_gravity: Query<&LocalGravity>,
+  modes: Query<&MovementMode>,
+  speeds: Query<(&MaxSpeed, &Gait, Option<&AccelCurve>, Option<&TurnBoost>)>,
+  aim_constraints: Query<(&Parent, &AimConstraint)>,
 ) {
   // Precision is adjusted so that the example works with
   // both the `f32` and `f64` features. Otherwise you don't need this.
   let delta_time = time.delta_secs_f64().adjust_precision();
-  for event in movement_event_reader.read() {
+  let in_space = |entity: Entity| modes.get(entity).is_ok_and(|mode| *mode == MovementMode::Space);
+  let events: Vec<&PlayerAction> = movement_event_reader.read().collect();
+  let walking: HashSet<Entity> = events
+      .iter()
+      .filter_map(|event| match event {
+          PlayerAction::Walk(entity) => Some(*entity),
+          _ => None,
+      })
+      .collect();
+  for event in events {
       match event {
+          PlayerAction::Move(e, dir) if in_space(*e) => {
+              if let Ok((_, accel, _, aim, mut vel, _, _)) = controllers.get_mut(*e) {
+                  // Thrust along the aim, or against it to brake
+                  vel.0 += aim.direction() * dir * accel.0 * delta_time;
+              }
+          }
           PlayerAction::Move(e, dir) => {
-              if let Ok((_, accel, _, _, mut vel, _, _)) = controllers.get_mut(*e) {
-                  vel.x += dir * accel.0 * delta_time;
+              if let Ok((_, accel, _, _, mut vel, grounded, _)) = controllers.get_mut(*e) {
+                  let accel_mul = TerrainModifier::under(standing_on.get(*e).ok(), &terrain)
+                      .map_or(1.0, |modifier| modifier.accel_mul);
+                  // On the ground, move along the slope instead of into or off of it
+                  let normal = ground_normals
+                      .get(*e)
+                      .ok()
+                      .filter(|_| grounded)
+                      .map_or(Vector::Y, |normal| normal.0);
+                  let (max_speed, gait, curve, turn_boost) = match speeds.get(*e) {
+                      Ok((max_speed, gait, curve, turn_boost)) => {
+                          let multiplier = if walking.contains(e) {
+                              gait.walk_multiplier
+                          } else {
+                              gait.run_multiplier
+                          };
+                          (max_speed.0 * multiplier, multiplier, curve.copied(), turn_boost)
+                      }
+                      Err(_) => (Scalar::INFINITY, 1.0, None, None),
+                  };
+                  let tangent = Vector::new(normal.y, -normal.x);
+                  // Brake harder when the input points against the current velocity. Slippery
+                  // ground makes turning around as hard as getting going.
+                  if let Some(turn_boost) = turn_boost {
+                      let along = tangent * dir.signum();
+                      let speed = vel.0.dot(along);
+                      if speed < 0.0 {
+                          let share = (turn_boost.strength * accel_mul * delta_time).min(1.0);
+                          vel.0 -= along * speed * share;
+                      }
+                  }
+                  let mut delta = tangent
+                      * dir
+                      * accel.0
+                      * accel_mul
+                      * gait
+                      * delta_time;
+                  // Climbing is slower the steeper the slope
+                  if delta.y > 0.0 {
+                      delta *= normal.y.max(0.0);
+                  }
+                  let speed = vel.0.dot(delta.normalize_or_zero());
+                  if let Some(curve) = curve {
+                      delta *= curve.multiplier(speed / max_speed);
+                  }
+                  // Don't accelerate past the top speed, but don't brake faster movement either
+                  vel.0 += delta.clamp_length_max((max_speed - speed).max(0.0));
+              }
+          }
+          PlayerAction::Jump(e) if in_space(*e) => {
+              if let Ok((_, _, jump, aim, mut vel, _, _)) = controllers.get_mut(*e) {
+                  // Boost up to the jump speed along the aim, holding it doesn't add up
+                  let direction = aim.direction();
+                  let speed = vel.0.dot(direction);
+                  if speed < jump.0 {
+                      vel.0 += direction * (jump.0 - speed);
+                  }
               }
           }
           PlayerAction::Jump(e) => {
@@ -193,96 +876,638 @@ fn movement(
           }
           PlayerAction::Aim(e, x, y) => {
               if let Ok((_, _, _, mut aim, _, _, _)) = controllers.get_mut(*e) {
-                  let angle = y.atan2(*x) + std::f32::consts::PI / 2.0;
-                  aim.0 = Quat::from_rotation_z(angle);
+                  let mut direction = Vector::new(*x, *y);
+                  // No direction to aim in, so keep the last aim
+                  if direction == Vector::ZERO {
+                      continue;
+                  }
+                  if let Ok((_, origin)) = positions.get(*e) {
+                      let targets = positions
+                          .iter()
+                          .filter(|(entity, _)| entity != e)
+                          .map(|(_, transform)| transform.translation.truncate());
+                      direction =
+                          aim_assist.assist(origin.translation.truncate(), direction, targets);
+                  }
+                  let mut angle = direction.y.atan2(direction.x);
+                  let constraint = aim_constraints
+                      .iter()
+                      .find(|(parent, _)| parent.get() == *e)
+                      .map(|(_, constraint)| constraint);
+                  if let Some(constraint) = constraint {
+                      let previous = aim.direction();
+                      angle = constraint.clamp(angle, previous.y.atan2(previous.x));
+                  }
+                  aim.0 = Quat::from_rotation_z(angle + std::f32::consts::PI / 2.0);
               }
           }
-          PlayerAction::Fire(e) => {
+          PlayerAction::Fire(e, slot, pressure) => {
               if let Ok((_, _, _, _, _, _, mut fire)) = controllers.get_mut(*e) {
-                  fire.0 = 1.0;
+                  fire.press(*slot, *pressure);
               }
           }
+          PlayerAction::Stomp(e) => {
+              if let (Ok((_, _, _, _, mut vel, grounded, _)), Ok(stomp)) =
+                  (controllers.get_mut(*e), stomps.get(*e))
+              {
+                  if !grounded {
+                      // Slam towards whatever "down" currently is
+                      let down = local_gravity
+                          .get(*e)
+                          .ok()
+                          .and_then(|gravity| gravity.0.try_normalize())
+                          .unwrap_or(Vector::NEG_Y);
+                      vel.0 = down * stomp.velocity;
+                      commands.entity(*e).try_insert(Stomping);
+                  }
+              }
+          }
+          // Handled by the grapple module
+          PlayerAction::Grapple(_) | PlayerAction::ReleaseGrapple(_) => {}
+          // Handled by `glide`
+          PlayerAction::Glide(_) => {}
+          // Handled by the trajectory module
+          PlayerAction::TogglePreview(_) => {}
+          // Gathered before going through the events
+          PlayerAction::Walk(_) => {}
+          // Handled by the combat module
+          PlayerAction::Detonate(_) => {}
+      }
+  }
+}
+
+// Eases every character's gun rotation towards where it's aiming.
+fn smooth_aim(time: Res<Time>, mut query: Query<(&AimRotation, &mut SmoothedAim)>) {
+  for (aim, mut smoothed) in &mut query {
+      if smoothed.rate <= 0.0 {
+          smoothed.rotation = aim.0;
+          continue;
       }
+      // Frame rate independent exponential easing
+      let t = 1.0 - (-smoothed.rate * time.delta_secs()).exp();
+      smoothed.rotation = smoothed.rotation.slerp(aim.0, t);
   }
 }
 
+// How much of the owner's color projectiles take on, so that players can tell whose
+// shots are whose while the weapon's own color still shows through.
+const PROJECTILE_OWNER_TINT: f32 = 0.7;
+
+fn projectile_tint(weapon_color: Color, owner_color: Option<&CharacterColor>) -> Color {
+  owner_color.map_or(weapon_color, |owner| {
+      weapon_color.mix(&owner.0, PROJECTILE_OWNER_TINT)
+  })
+}
+
 fn apply_aim_to_gun(
-  mut controllers: Query<(Entity, &AimRotation, &mut FireImpulse)>,
-  mut guns: Query<(&Parent, &mut Transform), With<Gun>>,
-  transforms: Query<&Transform, Without<Gun>>,
+  time: Res<Time>,
+  mut rng: ResMut<GameRng>,
+  mut controllers: Query<(
+      Entity,
+      &AimRotation,
+      Option<&SmoothedAim>,
+      &mut FireImpulse,
+      &mut LinearVelocity,
+      Has<Grounded>,
+  )>,
+  mut guns: Query<
+      (
+          Entity,
+          &Parent,
+          &mut Transform,
+          &mut Weapon,
+          &mut Spread,
+          Option<&GunVisual>,
+          Option<&mut Heat>,
+          Option<&SelfImpulse>,
+      ),
+      With<Gun>,
+  >,
+  shooters: Query<(&Transform, Option<&CharacterColor>, Option<&Loadout>), Without<Gun>>,
   mut commands: Commands,
+  mut shot_event_writer: EventWriter<ShotFired>,
+  mut bash_event_writer: EventWriter<GunBash>,
+  match_config: Res<MatchConfig>,
 ) {
-  for (parent, mut transform) in &mut guns {
-      let bullet_transform = if let Ok(parent_transform) = transforms.get(parent.get()) {
-          parent_transform.clone()
-      } else {
-          Transform::default()
+  for (gun, parent, mut transform, mut weapon, mut spread, visual, mut heat, self_impulse) in
+      &mut guns
+  {
+      weapon.cooldown.tick(time.delta());
+      weapon.bash_cooldown.tick(time.delta());
+      // The character may have been despawned this frame, in which case there is
+      // nothing to aim and no sensible place to spawn a bullet from.
+      let Ok((bullet_transform, owner_color, loadout)) = shooters.get(parent.get()) else {
+          continue;
       };
-      if let Ok((_, aim, mut fire)) = controllers.get_mut(parent.get()) {
-          transform.rotation = aim.0;
-          if fire.0 > 0.0 {
-              let adjusted_aim = aim.0 * Quat::from_rotation_z(-std::f32::consts::FRAC_PI_2); // Rotate by 90 degrees
-              let velocity = (adjusted_aim * Vec3::new(0.0, 0.0, 0.0)).truncate();
-              let impulse_vector = (adjusted_aim * Vec3::new(500.0, 0.0, 0.0)).truncate(); // Increased impulse value
-              println!("Fire impulse: {:?}", fire.0);
-              commands.spawn((
-                  Projectile {
-                      //velocity: aim.0 * Vec2::new(500.0, 0.0), // Set velocity based on the angle
-                      //velocity: (aim.0 * Vec3::new(500.0, 0.0, 0.0)).truncate(), // Set velocity based on the angle
-                      velocity: velocity,
-                      lifetime: 200.0,
-                  },
-                  Sprite {
-                      color: Color::WHITE,
-                      custom_size: Some(Vec2::new(30.0, 30.0)),
-                      ..default()
-                  },
-                  Transform {
-                      translation: bullet_transform.translation, // Spawn at the gun's position
-                      rotation: transform.rotation,
-                      ..default()
+      // Characters without a loadout only have the one gun
+      let slot = match loadout {
+          Some(loadout) => match loadout.slot_of(gun) {
+              Some(slot) => slot,
+              None => continue,
+          },
+          None => WeaponSlot::Primary,
+      };
+      if let Ok((_, aim, smoothed, fire, mut shooter_velocity, grounded)) =
+          controllers.get_mut(parent.get())
+      {
+          let aim = smoothed.map_or(aim.0, |smoothed| smoothed.rotation);
+          transform.rotation = aim;
+          let trigger = fire.get(slot);
+          // An empty gun is swung at whoever is in front instead
+          if trigger > 0.0 && match_config.gun_bash && weapon.can_bash() {
+              weapon.bash_cooldown.reset();
+              bash_event_writer.send(GunBash {
+                  attacker: parent.get(),
+                  origin: bullet_transform.translation.truncate(),
+                  direction: (aim * Vec3::NEG_Y).truncate(),
+              });
+          }
+          let cool_enough = heat.as_ref().map_or(true, |heat| heat.can_fire());
+          if trigger > 0.0 && weapon.can_fire() && cool_enough {
+              let _span = trace_span!("fire", shooter = ?parent.get()).entered();
+              weapon.consume_shot();
+              if let Some(heat) = heat.as_mut() {
+                  heat.add_shot();
+              }
+              let adjusted_aim = aim
+                  * Quat::from_rotation_z(-std::f32::consts::FRAC_PI_2 + spread.random_angle(&mut rng, grounded)); // Rotate by 90 degrees
+              spread.bloom();
+              let impulse_vector = (adjusted_aim * Vec3::new(weapon.muzzle_velocity, 0.0, 0.0)).truncate();
+              let muzzle_distance = weapon.muzzle_distance(visual);
+              let muzzle = bullet_transform.translation + adjusted_aim * Vec3::new(muzzle_distance, 0.0, 0.0);
+              debug!("Fire impulse: {:?}", trigger);
+              let mut projectile = commands.spawn_empty();
+              reset_projectile(
+                  &mut projectile,
+                  Shot {
+                      owner: parent.get(),
+                      transform: Transform {
+                          translation: muzzle, // Spawn just outside the shooter
+                          rotation: transform.rotation,
+                          ..default()
+                      },
+                      velocity: impulse_vector,
+                      lifetime: weapon.projectile_lifetime,
+                      damage: weapon.damage,
+                      falloff: weapon.kind.falloff(),
+                      blast_radius: weapon.kind.blast_radius(),
+                      color: projectile_tint(weapon.projectile_color, owner_color),
+                      size: weapon.projectile_size,
+                      collider: Collider::rectangle(weapon.projectile_size.x, weapon.projectile_size.y),
+                      mass: weapon.projectile_mass,
+                      gravity_scale: weapon.kind.gravity_scale(),
                   },
-                  Mass(10.0),
-                  RigidBody::Dynamic,
-                  Collider::rectangle(30.0, 30.0),
-                  LinearVelocity(impulse_vector),
-              ));
+              );
+              if let Some(splitting) = weapon.kind.splitting() {
+                  projectile.insert(splitting);
+              }
+              if weapon.kind.sticky() {
+                  projectile.insert(Sticky::default());
+              }
+              // Heavy weapons shove the shooter back the other way
+              if let Some(self_impulse) = self_impulse {
+                  let shot_direction = impulse_vector.normalize_or_zero();
+                  shooter_velocity.0 -= shot_direction * self_impulse.for_shooter(grounded);
+              }
+              shot_event_writer.send(ShotFired {
+                  shooter: parent.get(),
+                  weapon: weapon.kind,
+              });
+          }
+      }
+  }
+  // Triggers are held down by sending `PlayerAction::Fire` every frame
+  for (_, _, _, mut fire, _, _) in &mut controllers {
+      fire.release();
+  }
+}
+
+// Keeps characters from falling faster than their terminal velocity.
+fn clamp_fall_speed(
+  mut query: Query<(
+      &TerminalVelocity,
+      &mut LinearVelocity,
+      Option<&Glide>,
+      Has<Gliding>,
+      Option<&MovementMode>,
+  )>,
+) {
+  for (terminal_velocity, mut linear_velocity, glide, gliding, mode) in &mut query {
+      if mode == Some(&MovementMode::Space) {
+          continue;
+      }
+      let terminal_velocity = match glide {
+          Some(glide) if gliding => terminal_velocity.0 * glide.gravity_scale,
+          _ => terminal_velocity.0,
+      };
+      linear_velocity.y = linear_velocity.y.max(-terminal_velocity);
+  }
+}
+
+// Sets each character's gravity scale for the current phase of its jump. Gliding
+// characters are left alone, `glide` takes care of their gravity.
+fn apply_jump_gravity(
+  mut query: Query<
+      (
+          &JumpGravity,
+          &mut GravityScale,
+          &LinearVelocity,
+          Option<&LocalGravity>,
+          Option<&MovementMode>,
+          Has<Grounded>,
+      ),
+      Without<Gliding>,
+  >,
+) {
+  for (jump_gravity, mut gravity_scale, linear_velocity, local_gravity, mode, grounded) in
+      &mut query
+  {
+      if mode == Some(&MovementMode::Space) {
+          continue;
+      }
+      let up = local_gravity
+          .and_then(|gravity| (-gravity.0).try_normalize())
+          .unwrap_or(Vector::Y);
+      let scale = jump_gravity.scale(linear_velocity.0.dot(up), grounded);
+      gravity_scale.set_if_neq(GravityScale(scale));
+  }
+}
+
+// Starts gliding when jump is held while falling, and stops as soon as it's let go
+// or the character lands or starts rising again.
+fn glide(
+  mut commands: Commands,
+  mut movement_event_reader: EventReader<PlayerAction>,
+  mut query: Query<(
+      Entity,
+      &Glide,
+      &mut GravityScale,
+      &LinearVelocity,
+      Option<&LocalGravity>,
+      Option<&Gliding>,
+      Has<Grounded>,
+  )>,
+) {
+  let held: HashSet<Entity> = movement_event_reader
+      .read()
+      .filter_map(|event| match event {
+          PlayerAction::Glide(entity) => Some(*entity),
+          _ => None,
+      })
+      .collect();
+  for (entity, glide, mut gravity_scale, linear_velocity, local_gravity, gliding, grounded) in
+      &mut query
+  {
+      let down = local_gravity.map_or(Vector::NEG_Y, |gravity| gravity.0);
+      let falling = linear_velocity.0.dot(down) > 0.0;
+      let wants_to_glide = held.contains(&entity) && !grounded && falling;
+      match gliding {
+          None if wants_to_glide => {
+              commands.entity(entity).try_insert(Gliding {
+                  previous_gravity_scale: gravity_scale.0,
+              });
+              gravity_scale.0 *= glide.gravity_scale;
+          }
+          Some(gliding) if !wants_to_glide => {
+              gravity_scale.0 = gliding.previous_gravity_scale;
+              commands.entity(entity).remove::<Gliding>();
           }
-          fire.0 = 0.0;
+          _ => {}
       }
   }
 }
 
 // Slows down movement in the X direction.
-fn apply_movement_damping(mut query: Query<(&MovementDampingFactor, &mut LinearVelocity)>) {
-  for (damping_factor, mut linear_velocity) in &mut query {
-      // We could use `LinearDamping`, but we don't want to dampen movement along the Y axis
-      linear_velocity.x *= damping_factor.0;
+// Damping factors are the share of speed kept every 1/60 of a second, the frame rate they
+// were tuned at. They're scaled to the actual time step so they work the same at any rate.
+const DAMPING_REFERENCE_RATE: Scalar = 60.0;
+
+fn apply_movement_damping(
+  time: Res<Time>,
+  mut query: Query<(
+      &MovementDampingFactor,
+      &mut LinearVelocity,
+      Option<&PlatformVelocity>,
+      Option<&StandingOn>,
+      Option<&MovementMode>,
+  )>,
+  terrain: Query<&TerrainModifier>,
+) {
+  let steps = time.delta_secs_f64().adjust_precision() * DAMPING_REFERENCE_RATE;
+  for (damping_factor, mut linear_velocity, platform_velocity, standing_on, mode) in &mut query {
+      // Nothing slows you down in space
+      if mode == Some(&MovementMode::Space) {
+          continue;
+      }
+      let damping_factor = match TerrainModifier::under(standing_on, &terrain) {
+          Some(modifier) => (1.0 - (1.0 - damping_factor.0) * modifier.damping_mul).clamp(0.0, 1.0),
+          None => damping_factor.0,
+      }
+      .powf(steps);
+      // We could use `LinearDamping`, but we don't want to dampen movement along the Y axis.
+      // Only movement relative to the ground is damped, so riders keep up with their platform.
+      let platform_x = platform_velocity.map_or(0.0, |platform| platform.0.x);
+      linear_velocity.x = platform_x + (linear_velocity.x - platform_x) * damping_factor;
+  }
+}
+
+// Makes riders inherit the velocity of the kinematic platform below them.
+fn carry_platform_riders(
+  mut riders: Query<
+      (&StandingOn, &Position, &mut LinearVelocity, &mut PlatformVelocity),
+      With<MovingPlatformRider>,
+  >,
+  platforms: Query<
+      (&RigidBody, &Position, Option<&LinearVelocity>, Option<&AngularVelocity>),
+      Without<MovingPlatformRider>,
+  >,
+) {
+  for (standing_on, position, mut linear_velocity, mut platform_velocity) in &mut riders {
+      let platform = standing_on
+          .0
+          .and_then(|entity| platforms.get(entity).ok())
+          .filter(|(rigid_body, ..)| rigid_body.is_kinematic());
+      let Some((_, platform_position, platform_linear, platform_angular)) = platform else {
+          // Keep the momentum when leaving the platform (e.g. jumping off),
+          // but stop tracking it
+          platform_velocity.0 = Vector::ZERO;
+          continue;
+      };
+      // Velocity of the platform at the rider's position: linear plus the
+      // tangential contribution of its rotation
+      let offset = position.0 - platform_position.0;
+      let surface_velocity = platform_linear.map_or(Vector::ZERO, |velocity| velocity.0)
+          + Vector::new(-offset.y, offset.x) * platform_angular.map_or(0.0, |velocity| velocity.0);
+      // Only apply the change since last frame so the velocity doesn't pile up
+      linear_velocity.0 += surface_velocity - platform_velocity.0;
+      platform_velocity.0 = surface_velocity;
   }
 }
 
 fn update_grounded(
   mut commands: Commands,
   mut query: Query<
-      (Entity, &ShapeHits, &Rotation, Option<&MaxSlopeAngle>),
+      (
+          Entity,
+          &ShapeHits,
+          &Rotation,
+          Option<&MaxSlopeAngle>,
+          Option<&mut StandingOn>,
+          Option<&mut GroundNormal>,
+          Option<&MovementMode>,
+      ),
       With<CharacterController>,
   >,
 ) {
-  for (entity, hits, rotation, max_slope_angle) in &mut query {
+  for (entity, hits, rotation, max_slope_angle, standing_on, ground_normal, mode) in &mut query {
+      let in_space = mode == Some(&MovementMode::Space);
       // The character is grounded if the shape caster has a hit with a normal
-      // that isn't too steep.
-      let is_grounded = hits.iter().any(|hit| {
+      // that isn't too steep. There is no ground in space.
+      let ground = hits.iter().filter(|_| !in_space).find(|hit| {
           if let Some(angle) = max_slope_angle {
               (rotation * -hit.normal2).angle_to(Vector::Y).abs() <= angle.0
           } else {
               true
           }
       });
+      let is_grounded = ground.is_some();
+
+      if let Some(mut standing_on) = standing_on {
+          standing_on.0 = ground.map(|hit| hit.entity);
+      }
+      if let Some(mut ground_normal) = ground_normal {
+          ground_normal.0 = ground.map_or(Vector::Y, |hit| rotation * -hit.normal2);
+      }
 
       if is_grounded {
-          commands.entity(entity).insert(Grounded);
+          commands.entity(entity).try_insert(Grounded);
       } else {
           commands.entity(entity).remove::<Grounded>();
       }
   }
-}
\ No newline at end of file
+}
+
+fn snap_to_ground(
+  mut query: Query<
+      (
+          &GroundSnap,
+          &ShapeHits,
+          &Rotation,
+          &MaxSlopeAngle,
+          &mut Position,
+          &mut LinearVelocity,
+          Option<&MovementMode>,
+      ),
+      With<CharacterController>,
+  >,
+) {
+  for (snap, hits, rotation, max_slope_angle, mut position, mut linear_velocity, mode) in
+      &mut query
+  {
+      if mode == Some(&MovementMode::Space) {
+          continue;
+      }
+      let up = rotation * Vector::Y;
+      if linear_velocity.0.dot(up) > GROUND_SNAP_MAX_RISE {
+          continue;
+      }
+      // Only snap onto ground the character could stand on
+      let Some(hit) = hits
+          .iter()
+          .find(|hit| (rotation * -hit.normal2).angle_to(Vector::Y).abs() <= max_slope_angle.0)
+      else {
+          continue;
+      };
+      if hit.distance <= GROUND_SNAP_SKIN || hit.distance > snap.max_distance {
+          continue;
+      }
+      position.0 -= up * (hit.distance - GROUND_SNAP_SKIN);
+      // Cancel whatever would carry the character off the surface again
+      let normal = rotation * -hit.normal2;
+      let outward = linear_velocity.0.dot(normal);
+      if outward > 0.0 {
+          linear_velocity.0 -= normal * outward;
+      }
+  }
+}
+
+// Makes characters slide down ground that is too steep to stand on, instead of
+// sticking to it with friction.
+fn slide_off_steep_slopes(
+  time: Res<Time>,
+  mut query: Query<(
+      &ShapeHits,
+      &Rotation,
+      &MaxSlopeAngle,
+      &SlideAcceleration,
+      &mut LinearVelocity,
+      Option<&MovementMode>,
+  )>,
+) {
+  let delta_time = time.delta_secs_f64().adjust_precision();
+  for (hits, rotation, max_slope_angle, slide_acceleration, mut linear_velocity, mode) in
+      &mut query
+  {
+      if mode == Some(&MovementMode::Space) {
+          continue;
+      }
+      let mut normals = hits.iter().map(|hit| rotation * -hit.normal2);
+      // Only slide when there is nothing flat enough to stand on
+      if normals.clone().any(|normal| normal.angle_to(Vector::Y).abs() <= max_slope_angle.0) {
+          continue;
+      }
+      let Some(normal) = normals.next() else {
+          continue;
+      };
+      let tangent = Vector::new(normal.y, -normal.x);
+      let downhill = if tangent.y < 0.0 { tangent } else { -tangent };
+      linear_velocity.0 += downhill * slide_acceleration.0 * delta_time;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::combat::GunBash;
+    use crate::game::{
+        spawn_controllable_character, CharacterClass, CharacterSize, SpawnConfig,
+    };
+    use crate::weapons::{Projectile, WeaponKind};
+
+    const WEAPONS: [WeaponKind; 6] = [
+        WeaponKind::Pistol,
+        WeaponKind::Rifle,
+        WeaponKind::Grenade,
+        WeaponKind::Orbiter,
+        WeaponKind::Cluster,
+        WeaponKind::Mine,
+    ];
+
+    // Fires `kind` once from a `class` character aiming at `angle`, and returns how far
+    // the projectile's center starts out from the shooter's collider.
+    fn muzzle_clearance(class: CharacterClass, kind: WeaponKind, angle: f32) -> f32 {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        world.insert_resource(GameRng::new(0));
+        world.insert_resource(MatchConfig::default());
+        world.init_resource::<Events<ShotFired>>();
+        world.init_resource::<Events<GunBash>>();
+        let config = SpawnConfig {
+            weapon: kind,
+            ..class.spawn_config()
+        };
+        let collider = config.size.collider();
+        let shooter = world
+            .run_system_once(move |mut commands: Commands| {
+                spawn_controllable_character(&mut commands, &config)
+            })
+            .unwrap();
+        let aim = Quat::from_rotation_z(angle + std::f32::consts::FRAC_PI_2);
+        let mut character = world.entity_mut(shooter);
+        character.get_mut::<AimRotation>().unwrap().0 = aim;
+        character.get_mut::<SmoothedAim>().unwrap().rotation = aim;
+        character.get_mut::<FireImpulse>().unwrap().press(WeaponSlot::Primary, 1.0);
+        world.run_system_once(apply_aim_to_gun).unwrap();
+
+        let mut projectiles = world.query_filtered::<&Transform, With<Projectile>>();
+        let projectile = projectiles.single(&world).translation.truncate();
+        let shooter_position = world.get::<Transform>(shooter).unwrap().translation.truncate();
+        collider.distance_to_point(shooter_position, Rotation::default(), projectile, true)
+    }
+
+    #[test]
+    fn projectiles_spawn_outside_the_shooter() {
+        for class in CharacterClass::ALL {
+            for kind in WEAPONS {
+                for step in 0..8 {
+                    let angle = step as f32 * std::f32::consts::FRAC_PI_4;
+                    let clearance = muzzle_clearance(class, kind, angle);
+                    let half_size = Weapon::new(kind).projectile_size.max_element() / 2.0;
+                    assert!(
+                        clearance > half_size,
+                        "{kind:?} from {class:?} at {angle} starts {clearance} out"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ground_casters_only_just_fit_inside_the_collider() {
+        let sizes = CharacterClass::ALL
+            .map(|class| class.spawn_config().size)
+            .into_iter()
+            .chain([
+                CharacterSize {
+                    radius: 4.0,
+                    length: 0.0,
+                },
+                CharacterSize {
+                    radius: 40.0,
+                    length: 80.0,
+                },
+            ]);
+        for size in sizes {
+            let bundle = CharacterControllerBundle::new(size.collider());
+            let collider_size = bundle.collider.aabb(Vector::ZERO, Rotation::default()).size();
+            let caster_size = bundle
+                .ground_caster
+                .shape
+                .aabb(Vector::ZERO, Rotation::default())
+                .size();
+            // Touching walls mustn't count as ground, but the ground still has to be found
+            assert!(caster_size.cmplt(collider_size).all(), "{size:?}");
+            assert!(caster_size.cmpgt(collider_size * 0.95).all(), "{size:?}");
+        }
+    }
+
+    #[test]
+    fn releasing_the_aim_keeps_the_last_aim() {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        world.init_resource::<AimAssist>();
+        world.init_resource::<Events<PlayerAction>>();
+        let character = world
+            .run_system_once(|mut commands: Commands| {
+                spawn_controllable_character(&mut commands, &SpawnConfig::default())
+            })
+            .unwrap();
+        world.send_event(PlayerAction::Aim(character, -1.0, 1.0));
+        world.run_system_once(movement).unwrap();
+        let aim = world.get::<AimRotation>(character).unwrap().0;
+        assert!(aim.angle_between(Quat::IDENTITY) > 0.1);
+        world.resource_mut::<Events<PlayerAction>>().clear();
+
+        // A released stick sends nothing, or a zero aim from a remote client
+        world.run_system_once(movement).unwrap();
+        assert_eq!(world.get::<AimRotation>(character).unwrap().0, aim);
+        world.send_event(PlayerAction::Aim(character, 0.0, 0.0));
+        world.run_system_once(movement).unwrap();
+        assert_eq!(world.get::<AimRotation>(character).unwrap().0, aim);
+    }
+
+    #[test]
+    fn movement_params_within_range_are_kept() {
+        assert_eq!(clamp_movement_param("damping", 0.5, 0.0, 1.0), 0.5);
+        assert_eq!(clamp_movement_param("damping", 0.0, 0.0, 1.0), 0.0);
+        assert_eq!(clamp_movement_param("damping", 1.0, 0.0, 1.0), 1.0);
+    }
+
+    #[test]
+    fn movement_params_out_of_range_are_clamped() {
+        assert_eq!(clamp_movement_param("damping", -0.1, 0.0, 1.0), 0.0);
+        assert_eq!(clamp_movement_param("damping", 1.5, 0.0, 1.0), 1.0);
+        assert_eq!(clamp_movement_param("jump impulse", -800.0, 0.0, Scalar::MAX), 0.0);
+        assert_eq!(
+            clamp_movement_param("jump impulse", Scalar::INFINITY, 0.0, Scalar::MAX),
+            Scalar::MAX
+        );
+    }
+
+    #[test]
+    fn nan_movement_params_fall_back_to_the_minimum() {
+        assert_eq!(clamp_movement_param("damping", Scalar::NAN, 0.0, 1.0), 0.0);
+    }
+}
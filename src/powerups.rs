@@ -0,0 +1,248 @@
+use avian2d::prelude::*;
+use bevy::prelude::*;
+
+use crate::combat::Shield;
+use crate::layers;
+use crate::player::CharacterController;
+use crate::triggers::{TriggerZone, ZoneEntered};
+
+pub struct PowerupPlugin;
+
+impl Plugin for PowerupPlugin {
+    fn build(&self, app: &mut App) {
+        // Picking up powerups changes how characters play, so it's timed by fixed updates
+        app.add_systems(
+            FixedUpdate,
+            (
+                pull_powerups,
+                collect_powerups,
+                respawn_powerups,
+                expire_bouncy,
+                expire_magnets,
+            )
+                .chain(),
+        )
+        .add_systems(Update, draw_shields);
+    }
+}
+
+// What a powerup does to the character who picks it up.
+#[derive(Clone, Copy, Debug)]
+pub enum PowerupKind {
+    // Makes the character bounce off surfaces with `restitution` for `secs`.
+    Bouncy { restitution: f32, secs: f32 },
+    // Pulls pickups within `radius` toward the character at `strength` pixels per second,
+    // for `secs`.
+    Magnet { radius: f32, strength: f32, secs: f32 },
+    // Gives the character a `Shield` that absorbs `amount` damage.
+    Shield { amount: f32 },
+}
+
+impl PowerupKind {
+    pub fn color(&self) -> Color {
+        match self {
+            PowerupKind::Bouncy { .. } => Color::srgb(0.3, 1.0, 0.5),
+            PowerupKind::Magnet { .. } => Color::srgb(0.9, 0.2, 0.3),
+            PowerupKind::Shield { .. } => Color::srgb(0.3, 0.6, 1.0),
+        }
+    }
+}
+
+// A pickup that grants its powerup to the first character to touch it, then comes back
+// after `respawn` has run out.
+#[derive(Component)]
+#[require(TriggerZone)]
+pub struct Powerup {
+    pub kind: PowerupKind,
+    pub respawn: Timer,
+    pub available: bool,
+    // Where the pickup comes back to after a magnet has pulled it away.
+    pub home: Vec2,
+}
+
+const POWERUP_RADIUS: f32 = 10.0;
+const POWERUP_RESPAWN_SECS: f32 = 15.0;
+
+pub fn spawn_powerup(commands: &mut Commands, position: Vec2, kind: PowerupKind) -> Entity {
+    commands
+        .spawn((
+            Sprite {
+                color: kind.color(),
+                custom_size: Some(Vec2::splat(POWERUP_RADIUS * 2.0)),
+                ..default()
+            },
+            Transform::from_translation(position.extend(0.5)),
+            Collider::circle(POWERUP_RADIUS),
+            layers::pickup(),
+            Powerup {
+                kind,
+                respawn: Timer::from_seconds(POWERUP_RESPAWN_SECS, TimerMode::Once),
+                available: true,
+                home: position,
+            },
+        ))
+        .id()
+}
+
+// A temporary bounciness from a `PowerupKind::Bouncy`. The character's own restitution
+// is put back once the timer runs out.
+#[derive(Component)]
+pub struct Bouncy {
+    pub original: Restitution,
+    pub timer: Timer,
+}
+
+fn collect_powerups(
+    mut commands: Commands,
+    mut entered_reader: EventReader<ZoneEntered>,
+    mut powerups: Query<(&mut Powerup, &mut Visibility)>,
+    mut characters: Query<(&Restitution, Option<&mut Bouncy>), With<CharacterController>>,
+) {
+    for ZoneEntered(zone, body) in entered_reader.read() {
+        let Ok((mut powerup, mut visibility)) = powerups.get_mut(*zone) else {
+            continue;
+        };
+        let Ok((restitution, bouncy)) = characters.get_mut(*body) else {
+            continue;
+        };
+        if !powerup.available {
+            continue;
+        }
+        powerup.available = false;
+        powerup.respawn.reset();
+        *visibility = Visibility::Hidden;
+        match powerup.kind {
+            PowerupKind::Bouncy {
+                restitution: bounce,
+                secs,
+            } => {
+                // Picking up another one only extends the current one
+                let original = bouncy.map_or(*restitution, |bouncy| bouncy.original);
+                commands.entity(*body).try_insert((
+                    Restitution::new(bounce).with_combine_rule(CoefficientCombine::Max),
+                    Bouncy {
+                        original,
+                        timer: Timer::from_seconds(secs, TimerMode::Once),
+                    },
+                ));
+            }
+            PowerupKind::Magnet {
+                radius,
+                strength,
+                secs,
+            } => {
+                commands.entity(*body).try_insert(Magnet {
+                    radius,
+                    strength,
+                    timer: Timer::from_seconds(secs, TimerMode::Once),
+                });
+            }
+            PowerupKind::Shield { amount } => {
+                commands.entity(*body).try_insert(Shield::new(amount));
+            }
+        }
+    }
+}
+
+fn respawn_powerups(
+    time: Res<Time>,
+    mut powerups: Query<(&mut Powerup, &mut Visibility, &mut Transform)>,
+) {
+    for (mut powerup, mut visibility, mut transform) in &mut powerups {
+        if !powerup.available && powerup.respawn.tick(time.delta()).finished() {
+            powerup.available = true;
+            *visibility = Visibility::Inherited;
+            transform.translation = powerup.home.extend(transform.translation.z);
+        }
+    }
+}
+
+fn expire_bouncy(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut characters: Query<(Entity, &mut Bouncy)>,
+) {
+    for (entity, mut bouncy) in &mut characters {
+        if bouncy.timer.tick(time.delta()).finished() {
+            commands
+                .entity(entity)
+                .try_insert(bouncy.original)
+                .remove::<Bouncy>();
+        }
+    }
+}
+
+// A temporary pull on nearby pickups from a `PowerupKind::Magnet`, so the character
+// collects them without having to reach them.
+#[derive(Component)]
+pub struct Magnet {
+    pub radius: f32,
+    pub strength: f32,
+    pub timer: Timer,
+}
+
+// Pickups are static sensors, so they're moved directly rather than pushed. They're
+// collected the usual way once they reach the character.
+fn pull_powerups(
+    time: Res<Time>,
+    magnets: Query<(&Magnet, &Transform), Without<Powerup>>,
+    mut powerups: Query<(&Powerup, &mut Transform)>,
+) {
+    for (powerup, mut transform) in &mut powerups {
+        if !powerup.available {
+            continue;
+        }
+        let position = transform.translation.truncate();
+        // The closest magnet in range wins
+        let closest = magnets
+            .iter()
+            .map(|(magnet, magnet_transform)| {
+                (magnet, magnet_transform.translation.truncate() - position)
+            })
+            .filter(|(magnet, offset)| offset.length() <= magnet.radius)
+            .min_by(|(_, a), (_, b)| a.length_squared().total_cmp(&b.length_squared()));
+        let Some((magnet, offset)) = closest else {
+            continue;
+        };
+        let step = (magnet.strength * time.delta_secs()).min(offset.length());
+        transform.translation += (offset.normalize_or_zero() * step).extend(0.0);
+    }
+}
+
+fn expire_magnets(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut characters: Query<(Entity, &mut Magnet)>,
+) {
+    for (entity, mut magnet) in &mut characters {
+        if magnet.timer.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Magnet>();
+        }
+    }
+}
+
+// How far out the shield ring is drawn when the shield is full and when it's about to break.
+const SHIELD_RING_MAX_RADIUS: f32 = 40.0;
+const SHIELD_RING_MIN_RADIUS: f32 = 24.0;
+
+// Draws a ring around shielded characters that shrinks as the shield wears down, and
+// takes depleted shields away.
+fn draw_shields(
+    mut commands: Commands,
+    mut gizmos: Gizmos,
+    shields: Query<(Entity, &Shield, &Transform)>,
+) {
+    for (entity, shield, transform) in &shields {
+        if shield.is_depleted() {
+            commands.entity(entity).remove::<Shield>();
+            continue;
+        }
+        let radius = SHIELD_RING_MIN_RADIUS
+            + (SHIELD_RING_MAX_RADIUS - SHIELD_RING_MIN_RADIUS) * shield.fraction();
+        gizmos.circle_2d(
+            transform.translation.truncate(),
+            radius,
+            Color::srgba(0.3, 0.6, 1.0, 0.4 + 0.6 * shield.fraction()),
+        );
+    }
+}
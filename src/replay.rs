@@ -0,0 +1,355 @@
+use bevy::prelude::*;
+
+use crate::player::PlayerAction;
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ActionLog::default())
+            .insert_resource(ReplayTick::default())
+            .add_systems(Update, toggle_replay)
+            .add_systems(FixedFirst, advance_tick)
+            // Replayed actions go out at the start of their tick, and the recording
+            // picks up everything that went out on the tick as it's applied
+            .add_systems(
+                FixedPreUpdate,
+                play_back_actions.run_if(resource_exists::<ReplayPlayer>),
+            )
+            .add_systems(FixedUpdate, record_actions);
+    }
+}
+
+// Counts fixed updates, which unlike frames happen at the same rate on every machine.
+#[derive(Resource, Default)]
+pub struct ReplayTick(pub u32);
+
+// The `PlayerAction`s sent on one fixed update, in order. This is the unit actions are
+// recorded and sent over the network in, with the tick to put them back in order by.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "net", derive(serde::Serialize, serde::Deserialize))]
+pub struct ActionFrame {
+    pub tick: u32,
+    pub actions: Vec<PlayerAction>,
+}
+
+#[cfg(feature = "net")]
+impl ActionFrame {
+    // Packs the frame into as few bytes as it takes to send it.
+    pub fn pack(&self) -> Result<Vec<u8>, bincode::Error> {
+        bincode::serialize(self)
+    }
+
+    pub fn unpack(bytes: &[u8]) -> Result<Self, bincode::Error> {
+        bincode::deserialize(bytes)
+    }
+}
+
+// Every `PlayerAction` sent while recording, by the tick since the recording started.
+// F10 starts and stops a recording, F11 replays the last one.
+#[derive(Resource, Default)]
+pub struct ActionLog {
+    pub recording: bool,
+    pub frames: Vec<ActionFrame>,
+    start_tick: u32,
+}
+
+impl ActionLog {
+    pub fn action_count(&self) -> usize {
+        self.frames.iter().map(|frame| frame.actions.len()).sum()
+    }
+}
+
+// Feeds the actions of a recorded `ActionLog` back in on the ticks they were recorded on.
+// Input systems are disabled while it's around, and everything actions set in motion runs
+// on fixed updates, so with the same seed and starting state the replay plays out just
+// like the original at any frame rate. Joining isn't an action, so
+// characters have to be in place before starting the replay.
+#[derive(Resource)]
+pub struct ReplayPlayer {
+    frames: Vec<ActionFrame>,
+    start_tick: u32,
+    // The next frame to send.
+    cursor: usize,
+}
+
+impl ReplayPlayer {
+    // Replays `log` as if it had started recording on `tick`, so its first actions go
+    // out on the next fixed update.
+    pub fn new(log: &ActionLog, tick: u32) -> Self {
+        Self {
+            frames: log.frames.clone(),
+            start_tick: tick,
+            cursor: 0,
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.cursor >= self.frames.len()
+    }
+}
+
+// Run condition for systems reading live input, which a replay takes over from.
+pub fn not_replaying(replay: Option<Res<ReplayPlayer>>) -> bool {
+    replay.is_none()
+}
+
+fn advance_tick(mut tick: ResMut<ReplayTick>) {
+    tick.0 = tick.0.wrapping_add(1);
+}
+
+fn toggle_replay(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    tick: Res<ReplayTick>,
+    mut log: ResMut<ActionLog>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F10) {
+        if log.recording {
+            log.recording = false;
+            info!("Recorded {} actions", log.action_count());
+        } else {
+            log.recording = true;
+            log.start_tick = tick.0;
+            log.frames.clear();
+            info!("Recording actions");
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::F11) {
+        // Don't record the replay itself
+        log.recording = false;
+        commands.insert_resource(ReplayPlayer::new(&log, tick.0));
+        info!("Replaying {} actions", log.action_count());
+    }
+}
+
+fn record_actions(
+    tick: Res<ReplayTick>,
+    mut log: ResMut<ActionLog>,
+    mut action_reader: EventReader<PlayerAction>,
+) {
+    if !log.recording {
+        action_reader.clear();
+        return;
+    }
+    let actions: Vec<PlayerAction> = action_reader.read().cloned().collect();
+    if actions.is_empty() {
+        return;
+    }
+    log.frames.push(ActionFrame {
+        tick: tick.0.wrapping_sub(log.start_tick),
+        actions,
+    });
+}
+
+fn play_back_actions(
+    mut commands: Commands,
+    tick: Res<ReplayTick>,
+    mut replay: ResMut<ReplayPlayer>,
+    mut action_writer: EventWriter<PlayerAction>,
+) {
+    let relative_tick = tick.0.wrapping_sub(replay.start_tick);
+    while let Some(frame) = replay.frames.get(replay.cursor) {
+        if frame.tick > relative_tick {
+            return;
+        }
+        action_writer.send_batch(frame.actions.iter().cloned());
+        replay.cursor += 1;
+    }
+    if replay.finished() {
+        info!("Replay finished");
+        commands.remove_resource::<ReplayPlayer>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use avian2d::prelude::*;
+    use bevy::{ecs::system::RunSystemOnce, time::TimeUpdateStrategy};
+
+    use super::*;
+    use crate::bindings::InputBindings;
+    use crate::camera::Spectating;
+    use crate::combat::{DamageEvent, DeathEvent, GunBash};
+    use crate::debug::SandboxSettings;
+    use crate::game::{
+        spawn_controllable_character, MatchConfig, SpawnConfig, SpawnPoints, WorldBounds,
+    };
+    use crate::gravity::WorldGravity;
+    use crate::hud::Announcement;
+    use crate::layers;
+    use crate::lobby::{PlayerClasses, PlayerColors};
+    use crate::player::{CharacterControllerPlugin, PlayerAssignments, KEYBOARD_ID};
+    use crate::rng::GameRng;
+    use crate::testing::physics_app_with;
+    use crate::weapons::Projectile;
+    #[cfg(feature = "net")]
+    use crate::weapons::WeaponSlot;
+
+    // Fixed updates in the recording and the replay.
+    const TICKS: u32 = 128;
+
+    // The whole character controller with a keyboard player standing on a floor, and
+    // replays recording from the start.
+    fn app() -> (App, Entity) {
+        let mut app = physics_app_with(|app| {
+            app.add_plugins((CharacterControllerPlugin, ReplayPlugin))
+                .add_event::<Announcement>()
+                .add_event::<DamageEvent>()
+                .add_event::<DeathEvent>()
+                .add_event::<GunBash>()
+                .init_resource::<Assets<ColorMaterial>>()
+                .init_resource::<ButtonInput<KeyCode>>()
+                .init_resource::<InputBindings>()
+                .init_resource::<MatchConfig>()
+                .init_resource::<PlayerAssignments>()
+                .init_resource::<PlayerClasses>()
+                .init_resource::<PlayerColors>()
+                .init_resource::<SandboxSettings>()
+                .init_resource::<Spectating>()
+                .init_resource::<WorldBounds>()
+                .init_resource::<WorldGravity>()
+                .insert_resource(SpawnPoints(Vec::new()))
+                .insert_resource(GameRng::new(7))
+                .insert_resource(Gravity(Vec2::NEG_Y * 1000.0))
+                .insert_resource(ActionLog {
+                    recording: true,
+                    ..default()
+                });
+        });
+        let world = app.world_mut();
+        world.spawn((
+            RigidBody::Static,
+            Collider::rectangle(4000.0, 20.0),
+            Transform::from_xyz(0.0, -60.0, 0.0),
+            layers::world(),
+        ));
+        let character = world
+            .run_system_once(|mut commands: Commands| {
+                spawn_controllable_character(&mut commands, &SpawnConfig::default())
+            })
+            .unwrap();
+        world
+            .resource_mut::<PlayerAssignments>()
+            .players
+            .insert(KEYBOARD_ID, character);
+        (app, character)
+    }
+
+    // Runs frames `secs` long until `ticks` fixed updates have run, with `input` pressing
+    // keys before each frame.
+    fn run(
+        app: &mut App,
+        secs: f64,
+        ticks: u32,
+        mut input: impl FnMut(u32, &mut ButtonInput<KeyCode>),
+    ) {
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            secs,
+        )));
+        let mut frame = 0;
+        while app.world().resource::<ReplayTick>().0 < ticks {
+            let mut keys = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+            keys.clear();
+            input(frame, &mut keys);
+            app.update();
+            frame += 1;
+        }
+        assert_eq!(app.world().resource::<ReplayTick>().0, ticks);
+    }
+
+    // Where the character and every projectile ended up.
+    fn outcome(app: &mut App, character: Entity) -> (Vec2, Vec2, Vec<Vec2>) {
+        let world = app.world_mut();
+        let position = world.get::<Position>(character).unwrap().0;
+        let velocity = world.get::<LinearVelocity>(character).unwrap().0;
+        let mut projectiles: Vec<Vec2> = world
+            .query_filtered::<&Position, With<Projectile>>()
+            .iter(world)
+            .map(|position| position.0)
+            .collect();
+        projectiles.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+        (position, velocity, projectiles)
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn action_frames_survive_packing() {
+        let character = Entity::from_raw(7);
+        let frame = ActionFrame {
+            tick: u32::MAX - 3,
+            actions: vec![
+                PlayerAction::Move(character, -0.75),
+                PlayerAction::Jump(character),
+                PlayerAction::Aim(character, 0.6, -0.8),
+                PlayerAction::Fire(character, WeaponSlot::Primary, 0.5),
+                PlayerAction::Fire(character, WeaponSlot::Secondary, 1.0),
+                PlayerAction::Stomp(character),
+                PlayerAction::Grapple(character),
+                PlayerAction::ReleaseGrapple(character),
+                PlayerAction::Glide(character),
+                PlayerAction::TogglePreview(character),
+                PlayerAction::Walk(character),
+                PlayerAction::Detonate(character),
+            ],
+        };
+        let bytes = frame.pack().unwrap();
+        assert_eq!(ActionFrame::unpack(&bytes).unwrap(), frame);
+        // An empty frame still carries its tick
+        let empty = ActionFrame {
+            tick: 42,
+            actions: Vec::new(),
+        };
+        assert_eq!(ActionFrame::unpack(&empty.pack().unwrap()).unwrap(), empty);
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn truncated_action_frames_dont_unpack() {
+        let frame = ActionFrame {
+            tick: 1,
+            actions: vec![PlayerAction::Jump(Entity::from_raw(1))],
+        };
+        let bytes = frame.pack().unwrap();
+        assert!(ActionFrame::unpack(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn replays_end_up_where_the_recording_did() {
+        // Recorded at two fixed updates a frame, running back and forth, jumping and
+        // shooting as it goes
+        let (mut recording, character) = app();
+        run(&mut recording, 1.0 / 32.0, TICKS, |frame, keys| {
+            let (forward, back) = if (frame / 12) % 2 == 0 {
+                (KeyCode::KeyD, KeyCode::KeyA)
+            } else {
+                (KeyCode::KeyA, KeyCode::KeyD)
+            };
+            keys.release(back);
+            keys.press(forward);
+            for (key, every) in [(KeyCode::KeyF, 5), (KeyCode::Space, 9)] {
+                if frame % every == 0 {
+                    keys.press(key);
+                } else {
+                    keys.release(key);
+                }
+            }
+        });
+        let recorded = outcome(&mut recording, character);
+        assert!(recorded.0.x.abs() > 1.0, "the character didn't move");
+        assert!(!recorded.2.is_empty(), "nothing was fired");
+
+        // Replayed at half a fixed update a frame
+        let (mut replaying, replayed_character) = app();
+        assert_eq!(replayed_character, character);
+        let world = replaying.world_mut();
+        world.resource_mut::<ActionLog>().recording = false;
+        let replay = ReplayPlayer::new(recording.world().resource::<ActionLog>(), 0);
+        world.insert_resource(replay);
+        run(&mut replaying, 1.0 / 128.0, TICKS, |_, _| {});
+        assert!(!replaying.world().contains_resource::<ReplayPlayer>());
+        assert_eq!(outcome(&mut replaying, character), recorded);
+    }
+}
@@ -0,0 +1,71 @@
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::ops::Range;
+
+// The one source of randomness for gameplay. Everything random draws from it
+// rather than `thread_rng`, so that the same seed plays out the same way.
+#[derive(Resource)]
+pub struct GameRng {
+    seed: u64,
+    rng: ChaCha8Rng,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    // Seeds from the `GAME_SEED` environment variable if it's set, or randomly otherwise.
+    pub fn from_env() -> Self {
+        let seed = std::env::var("GAME_SEED")
+            .ok()
+            .and_then(|seed| seed.parse().ok())
+            .unwrap_or_else(rand::random);
+        info!("Game seed: {seed}");
+        Self::new(seed)
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // A float in `range`. Empty ranges give their start.
+    pub fn float(&mut self, range: Range<f32>) -> f32 {
+        if range.is_empty() {
+            return range.start;
+        }
+        self.rng.gen_range(range)
+    }
+
+    // An angle in radians, anywhere around the circle.
+    pub fn angle(&mut self) -> f32 {
+        self.float(0.0..std::f32::consts::TAU)
+    }
+
+    // A unit vector pointing in a random direction.
+    pub fn direction(&mut self) -> Vec2 {
+        Vec2::from_angle(self.angle())
+    }
+
+    // True with the given probability, from 0 to 1.
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.rng.gen_bool(probability.clamp(0.0, 1.0))
+    }
+}
+
+// Randomness for things that stand in for players, like bots. What they do goes out as
+// actions, which replays play back without them, so drawing from the `GameRng` would leave
+// a replay drawing different numbers. Seeded from it, so one seed still covers everything.
+#[derive(Deref, DerefMut)]
+pub struct InputRng(GameRng);
+
+impl FromWorld for InputRng {
+    fn from_world(world: &mut World) -> Self {
+        let seed = world.get_resource::<GameRng>().map_or(0, GameRng::seed);
+        Self(GameRng::new(seed.wrapping_add(1)))
+    }
+}
@@ -0,0 +1,274 @@
+use bevy::prelude::*;
+
+use crate::bindings::not_remapping;
+use crate::camera::Spectating;
+use crate::combat::{Debris, Destructible, ExplosionFlash};
+use crate::decals::{Decal, DecalPool};
+use crate::game::{spawn_props, MatchConfig, WorldBounds};
+use crate::player::{CharacterController, PlayerAssignments};
+use crate::powerups::Powerup;
+use crate::replay::ReplayPlayer;
+use crate::rng::GameRng;
+use crate::scoring::Scores;
+use crate::weapons::Projectile;
+
+pub struct RoundPlugin;
+
+impl Plugin for RoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<GameState>()
+            .add_systems(OnEnter(GameState::Playing), start_round)
+            .add_systems(Update, start_when_joined.run_if(in_state(GameState::Lobby)))
+            .add_systems(
+                Update,
+                (tick_round_timer, shrink_world_bounds, draw_world_bounds)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(Update, restart_match.run_if(not_remapping));
+    }
+}
+
+#[derive(States, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum GameState {
+    // Waiting for the first player to join.
+    #[default]
+    Lobby,
+    Playing,
+    // The round is over and has a winner.
+    GameOver,
+}
+
+// How fast the world bounds close in during sudden death, in pixels per second.
+const SUDDEN_DEATH_SHRINK_SPEED: f32 = 100.0;
+// How far from the center the world bounds stop closing in.
+const SUDDEN_DEATH_MIN_HALF_WIDTH: f32 = 150.0;
+const SUDDEN_DEATH_MIN_TOP: f32 = 0.0;
+
+// Time left in the current round.
+#[derive(Resource)]
+pub struct RoundTimer(pub Timer);
+
+// Present once the round timer has run out without a clear winner. The first
+// player to take the lead wins.
+#[derive(Resource)]
+pub struct SuddenDeath;
+
+fn start_round(mut commands: Commands, match_config: Res<MatchConfig>) {
+    commands.insert_resource(RoundTimer(Timer::from_seconds(
+        match_config.round_secs,
+        TimerMode::Once,
+    )));
+}
+
+fn start_when_joined(
+    assignments: Res<PlayerAssignments>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !assignments.players.is_empty() {
+        next_state.set(GameState::Playing);
+    }
+}
+
+// Clears out the match and goes back to the lobby when F5 is pressed, or Start
+// and North together on a gamepad. The world is put back the way it started: props are
+// rebuilt, powerups come back home and the game seed starts over.
+fn restart_match(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut assignments: ResMut<PlayerAssignments>,
+    mut scores: ResMut<Scores>,
+    mut spectating: ResMut<Spectating>,
+    mut bounds: ResMut<WorldBounds>,
+    mut decal_pool: ResMut<DecalPool>,
+    mut rng: ResMut<GameRng>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut powerups: Query<(&mut Powerup, &mut Visibility, &mut Transform)>,
+    leftovers: Query<
+        Entity,
+        Or<(
+            With<CharacterController>,
+            With<Projectile>,
+            With<Debris>,
+            With<ExplosionFlash>,
+            With<Decal>,
+            With<Destructible>,
+        )>,
+    >,
+) {
+    let restart = keyboard_input.just_pressed(KeyCode::F5)
+        || gamepads.iter().any(|gamepad| {
+            gamepad.pressed(GamepadButton::Start) && gamepad.just_pressed(GamepadButton::North)
+        });
+    if !restart {
+        return;
+    }
+    let mut despawned = 0;
+    for entity in &leftovers {
+        // Characters take their guns and grapple joints with them
+        commands.entity(entity).despawn_recursive();
+        despawned += 1;
+    }
+    info!("Restarting the match, cleared {} entities", despawned);
+    spawn_props(&mut commands);
+    for (mut powerup, mut visibility, mut transform) in &mut powerups {
+        powerup.available = true;
+        powerup.respawn.reset();
+        *visibility = Visibility::Inherited;
+        transform.translation = powerup.home.extend(transform.translation.z);
+    }
+    let seed = rng.seed();
+    *rng = GameRng::new(seed);
+    assignments.players.clear();
+    scores.0.clear();
+    spectating.players.clear();
+    *bounds = WorldBounds::default();
+    decal_pool.0.clear();
+    commands.remove_resource::<SuddenDeath>();
+    commands.remove_resource::<RoundTimer>();
+    commands.remove_resource::<ReplayPlayer>();
+    next_state.set(GameState::Lobby);
+}
+
+fn tick_round_timer(
+    mut commands: Commands,
+    time: Res<Time>,
+    scores: Res<Scores>,
+    sudden_death: Option<Res<SuddenDeath>>,
+    mut round_timer: ResMut<RoundTimer>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !round_timer.0.tick(time.delta()).finished() {
+        return;
+    }
+    match scores.leader() {
+        Some(winner) => {
+            info!("Player {} wins the round", winner.0);
+            next_state.set(GameState::GameOver);
+        }
+        None if sudden_death.is_none() => {
+            info!("Time's up without a clear winner, sudden death!");
+            commands.insert_resource(SuddenDeath);
+        }
+        None => {}
+    }
+}
+
+// Closes the world in from the sides and the top during sudden death.
+fn shrink_world_bounds(
+    time: Res<Time>,
+    sudden_death: Option<Res<SuddenDeath>>,
+    mut bounds: ResMut<WorldBounds>,
+) {
+    if sudden_death.is_none() {
+        return;
+    }
+    let step = SUDDEN_DEATH_SHRINK_SPEED * time.delta_secs();
+    bounds.min.x = (bounds.min.x + step).min(-SUDDEN_DEATH_MIN_HALF_WIDTH);
+    bounds.max.x = (bounds.max.x - step).max(SUDDEN_DEATH_MIN_HALF_WIDTH);
+    bounds.max.y = (bounds.max.y - step).max(SUDDEN_DEATH_MIN_TOP);
+}
+
+// Shows where the closing world bounds are during sudden death.
+fn draw_world_bounds(
+    sudden_death: Option<Res<SuddenDeath>>,
+    bounds: Res<WorldBounds>,
+    mut gizmos: Gizmos,
+) {
+    if sudden_death.is_none() {
+        return;
+    }
+    gizmos.rect_2d(
+        Isometry2d::from_translation((bounds.min + bounds.max) / 2.0),
+        bounds.max - bounds.min,
+        Color::srgb(0.9, 0.1, 0.1),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::combat::Damageable;
+    use crate::powerups::PowerupKind;
+    use crate::replay::ActionLog;
+    use crate::weapons::WeaponKind;
+
+    fn timer() -> Timer {
+        Timer::from_seconds(1.0, TimerMode::Once)
+    }
+
+    #[test]
+    fn restarting_clears_out_the_match() {
+        let mut world = World::new();
+        let mut keyboard_input = ButtonInput::<KeyCode>::default();
+        keyboard_input.press(KeyCode::F5);
+        world.insert_resource(keyboard_input);
+        world.init_resource::<PlayerAssignments>();
+        world.init_resource::<Scores>();
+        world.init_resource::<Spectating>();
+        world.init_resource::<WorldBounds>();
+        world.init_resource::<DecalPool>();
+        world.init_resource::<NextState<GameState>>();
+        world.insert_resource(GameRng::new(7));
+        world.insert_resource(ReplayPlayer::new(&ActionLog::default(), 0));
+
+        let planet = world.spawn(Transform::default()).id();
+        let character = world.spawn(CharacterController).id();
+        let gun = world.spawn(Transform::default()).set_parent(character).id();
+        let owner = character;
+        world.spawn(Projectile {
+            lifetime: 1.0,
+            max_lifetime: 1.0,
+            owner,
+            spawn_position: Vec2::ZERO,
+            damage: 1.0,
+            falloff: WeaponKind::Pistol.falloff(),
+            blast_radius: None,
+        });
+        world.spawn(Debris { timer: timer() });
+        world.spawn(ExplosionFlash { timer: timer() });
+        let decal = world.spawn(Decal { timer: timer() }).set_parent(planet).id();
+        world.resource_mut::<DecalPool>().0.push_back(decal);
+        // A crate that's been shot at, and a powerup someone picked up after a magnet
+        // pulled it away
+        world.spawn((Damageable::new(1.0), Destructible));
+        let home = Vec2::new(10.0, 20.0);
+        let powerup = world
+            .spawn((
+                Powerup {
+                    kind: PowerupKind::Shield { amount: 10.0 },
+                    respawn: timer(),
+                    available: false,
+                    home,
+                },
+                Visibility::Hidden,
+                Transform::from_xyz(50.0, 50.0, 0.5),
+            ))
+            .id();
+        world.resource_mut::<GameRng>().float(0.0..1.0);
+        assert_eq!(world.entities().len(), 9);
+
+        world.run_system_once(restart_match).unwrap();
+        // The planet, the powerup and a fresh set of props are left, without the mark on
+        // the planet
+        let props = world.query_filtered::<(), With<Destructible>>().iter(&world).count();
+        assert_eq!(props, 7);
+        assert_eq!(world.entities().len(), 2 + props as u32);
+        assert!(world.get_entity(gun).is_err());
+        assert!(world.get::<Children>(planet).map_or(true, |children| children.is_empty()));
+        assert!(world.resource::<DecalPool>().0.is_empty());
+        assert!(!world.contains_resource::<ReplayPlayer>());
+
+        let powerup = world.entity(powerup);
+        assert!(powerup.get::<Powerup>().unwrap().available);
+        assert_eq!(powerup.get::<Visibility>(), Some(&Visibility::Inherited));
+        let position = powerup.get::<Transform>().unwrap().translation;
+        assert_eq!(position, home.extend(0.5));
+        // The seed starts over
+        let first = GameRng::new(7).float(0.0..1.0);
+        assert_eq!(world.resource_mut::<GameRng>().float(0.0..1.0), first);
+    }
+}
@@ -0,0 +1,97 @@
+use bevy::{
+    input::gamepad::{GamepadRumbleIntensity, GamepadRumbleRequest},
+    prelude::*,
+};
+use std::time::Duration;
+
+use crate::combat::DamageEvent;
+use crate::player::PlayerAssignments;
+use crate::weapons::ShotFired;
+
+pub struct RumblePlugin;
+
+impl Plugin for RumblePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RumbleSettings::default())
+            .add_systems(Update, (rumble_on_fire, rumble_on_damage));
+    }
+}
+
+// Controller vibration settings shared by all gamepads.
+#[derive(Resource)]
+pub struct RumbleSettings {
+    pub enabled: bool,
+    // Damage that results in a full strength rumble.
+    pub max_damage: f32,
+}
+
+impl Default for RumbleSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_damage: 50.0,
+        }
+    }
+}
+
+// Finds the gamepad controlling the given character, if any.
+fn gamepad_for(
+    character: Entity,
+    assignments: &PlayerAssignments,
+    gamepads: &Query<Entity, With<Gamepad>>,
+) -> Option<Entity> {
+    let gamepad = assignments.gamepad_for(assignments.player_id(character)?)?;
+    // The gamepad may have been disconnected since
+    gamepads.contains(gamepad).then_some(gamepad)
+}
+
+fn rumble_on_fire(
+    settings: Res<RumbleSettings>,
+    assignments: Res<PlayerAssignments>,
+    gamepads: Query<Entity, With<Gamepad>>,
+    mut shot_event_reader: EventReader<ShotFired>,
+    mut rumble_requests: EventWriter<GamepadRumbleRequest>,
+) {
+    if !settings.enabled {
+        shot_event_reader.clear();
+        return;
+    }
+    for shot in shot_event_reader.read() {
+        if let Some(gamepad) = gamepad_for(shot.shooter, &assignments, &gamepads) {
+            rumble_requests.send(GamepadRumbleRequest::Add {
+                gamepad,
+                duration: Duration::from_millis(80),
+                intensity: GamepadRumbleIntensity {
+                    strong_motor: 0.0,
+                    weak_motor: shot.weapon.recoil(),
+                },
+            });
+        }
+    }
+}
+
+fn rumble_on_damage(
+    settings: Res<RumbleSettings>,
+    assignments: Res<PlayerAssignments>,
+    gamepads: Query<Entity, With<Gamepad>>,
+    mut damage_event_reader: EventReader<DamageEvent>,
+    mut rumble_requests: EventWriter<GamepadRumbleRequest>,
+) {
+    if !settings.enabled {
+        damage_event_reader.clear();
+        return;
+    }
+    for damage in damage_event_reader.read() {
+        if let Some(gamepad) = gamepad_for(damage.target, &assignments, &gamepads) {
+            let intensity = (damage.total() / settings.max_damage).clamp(0.2, 1.0);
+            rumble_requests.send(GamepadRumbleRequest::Add {
+                gamepad,
+                duration: Duration::from_millis(250),
+                intensity: GamepadRumbleIntensity {
+                    strong_motor: intensity,
+                    weak_motor: intensity,
+                },
+            });
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+use crate::combat::{CombatSet, DamageEvent, DeathEvent, LastHitBy};
+use crate::player::{CharacterController, PlayerId};
+use crate::weapons::ShotFired;
+
+pub struct ScoringPlugin;
+
+impl Plugin for ScoringPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Scores::default())
+            .insert_resource(PlayerStats::default())
+            .add_systems(
+                FixedUpdate,
+                (award_kills, count_hits)
+                    .after(CombatSet::Damage)
+                    .before(CombatSet::Cleanup),
+            )
+            .add_systems(Update, count_shots);
+    }
+}
+
+// Kills scored by each player, keyed by `PlayerId`.
+#[derive(Resource, Default)]
+pub struct Scores(pub HashMap<PlayerId, u32>);
+
+impl Scores {
+    // The player with the most kills, or `None` if nobody has scored or the lead is shared.
+    pub fn leader(&self) -> Option<PlayerId> {
+        let best = self.0.values().copied().max()?;
+        let mut leaders = self.0.iter().filter(|(_, kills)| **kills == best);
+        let (leader, _) = leaders.next()?;
+        leaders.next().is_none().then_some(*leader)
+    }
+}
+
+// Lifetime numbers for one player.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+    pub shots_fired: u32,
+    pub hits: u32,
+    pub kills: u32,
+    pub deaths: u32,
+    pub damage_dealt: f32,
+}
+
+impl Stats {
+    // The share of shots that hit something, from 0 to 1. A blast hitting several
+    // targets counts once for each, but accuracy never goes past 1.
+    pub fn accuracy(&self) -> f32 {
+        if self.shots_fired == 0 {
+            return 0.0;
+        }
+        (self.hits as f32 / self.shots_fired as f32).min(1.0)
+    }
+}
+
+// Stats of each player, keyed by `PlayerId`. Unlike `Scores` these carry over
+// from match to match for as long as the game runs.
+#[derive(Resource, Default)]
+pub struct PlayerStats(pub HashMap<PlayerId, Stats>);
+
+fn award_kills(
+    mut scores: ResMut<Scores>,
+    mut stats: ResMut<PlayerStats>,
+    mut death_event_reader: EventReader<DeathEvent>,
+    players: Query<&PlayerId>,
+    characters: Query<(), With<CharacterController>>,
+    last_hits: Query<&LastHitBy>,
+) {
+    for death in death_event_reader.read() {
+        // Falling to your death still counts for whoever hit you recently
+        let recent_attacker = last_hits
+            .get(death.victim)
+            .ok()
+            .map(|last_hit_by| last_hit_by.attacker);
+        let killer = death
+            .killer
+            .filter(|killer| *killer != death.victim)
+            .or(recent_attacker);
+        if let Ok(victim) = players.get(death.victim) {
+            stats.0.entry(*victim).or_default().deaths += 1;
+        }
+        let Some(killer) = killer else {
+            continue;
+        };
+        // Only killing other characters scores, not crates, dummies or yourself
+        if killer == death.victim || !characters.contains(death.victim) {
+            continue;
+        }
+        if let Ok(player) = players.get(killer) {
+            *scores.0.entry(*player).or_default() += 1;
+            stats.0.entry(*player).or_default().kills += 1;
+        }
+    }
+}
+
+fn count_shots(
+    mut stats: ResMut<PlayerStats>,
+    mut shot_event_reader: EventReader<ShotFired>,
+    players: Query<&PlayerId>,
+) {
+    for shot in shot_event_reader.read() {
+        if let Ok(player) = players.get(shot.shooter) {
+            stats.0.entry(*player).or_default().shots_fired += 1;
+        }
+    }
+}
+
+fn count_hits(
+    mut stats: ResMut<PlayerStats>,
+    mut damage_event_reader: EventReader<DamageEvent>,
+    players: Query<&PlayerId>,
+) {
+    for damage in damage_event_reader.read() {
+        let Some(source) = damage.source.filter(|source| *source != damage.target) else {
+            continue;
+        };
+        if let Ok(player) = players.get(source) {
+            let stats = stats.0.entry(*player).or_default();
+            stats.hits += 1;
+            stats.damage_dealt += damage.total();
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use avian2d::prelude::*;
+use bevy::{
+    ecs::system::{SystemParam, SystemState},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::combat::Damageable;
+use crate::player::{AimRotation, CharacterController, Grounded, PlayerId};
+use crate::weapons::{Loadout, Weapon};
+
+// Everything worth knowing about a character at one moment, in one place, for the HUD,
+// bots and eventually the network to read instead of querying each piece themselves.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerSnapshot {
+    pub entity: Entity,
+    // There are no teams, so characters are told apart by player. Bots and dummies have none.
+    pub player: Option<u32>,
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub grounded: bool,
+    pub health: f32,
+    pub max_health: f32,
+    // Shots left in the primary gun, or `None` for unlimited ammo.
+    pub ammo: Option<u32>,
+    // The angle being aimed at in radians, counterclockwise from the positive X axis.
+    pub aim_angle: f32,
+}
+
+// Reads `PlayerSnapshot`s from inside a system.
+#[derive(SystemParam)]
+pub struct PlayerSnapshots<'w, 's> {
+    characters: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static Transform,
+            &'static LinearVelocity,
+            &'static AimRotation,
+            Has<Grounded>,
+            Option<&'static PlayerId>,
+            Option<&'static Damageable>,
+            Option<&'static Loadout>,
+        ),
+        With<CharacterController>,
+    >,
+    weapons: Query<'w, 's, &'static Weapon>,
+}
+
+impl PlayerSnapshots<'_, '_> {
+    // The current state of `entity`, or `None` if it isn't a character.
+    pub fn get(&self, entity: Entity) -> Option<PlayerSnapshot> {
+        let (_, transform, velocity, aim, grounded, player, damageable, loadout) =
+            self.characters.get(entity).ok()?;
+        let ammo = loadout
+            .and_then(|loadout| self.weapons.get(loadout.primary).ok())
+            .and_then(|weapon| weapon.ammo);
+        Some(PlayerSnapshot {
+            entity,
+            player: player.map(|player| player.0),
+            position: transform.translation.truncate(),
+            velocity: velocity.0,
+            grounded,
+            health: damageable.map_or(0.0, |damageable| damageable.health),
+            max_health: damageable.map_or(0.0, |damageable| damageable.max),
+            ammo,
+            aim_angle: aim.direction().to_angle(),
+        })
+    }
+
+    // Snapshots of every character.
+    pub fn all(&self) -> Vec<PlayerSnapshot> {
+        self.characters
+            .iter()
+            .filter_map(|(entity, ..)| self.get(entity))
+            .collect()
+    }
+}
+
+// The current state of `entity` for code with direct `World` access, or `None` if it
+// isn't a character.
+pub fn player_snapshot(world: &mut World, entity: Entity) -> Option<PlayerSnapshot> {
+    let mut state = SystemState::<PlayerSnapshots>::new(world);
+    state.get(world).get(entity)
+}
@@ -0,0 +1,148 @@
+use bevy::{
+    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+};
+
+use crate::game::{spawn_controllable_character, SpawnConfig};
+use crate::input::ActionWriter;
+use crate::player::PlayerAction;
+use crate::rng::InputRng;
+use crate::weapons::{Heat, Projectile, Weapon, WeaponKind, WeaponSlot};
+
+pub struct StressTestPlugin;
+
+impl Plugin for StressTestPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+            app.add_plugins(FrameTimeDiagnosticsPlugin);
+        }
+        app.add_systems(Startup, spawn_frame_time_readout).add_systems(
+            Update,
+            (
+                toggle_stress_test,
+                drive_stress_dummies,
+                update_frame_time_readout,
+            )
+                .chain(),
+        );
+    }
+}
+
+// How many characters the stress test spawns, in a grid of this many columns.
+const STRESS_DUMMIES: usize = 200;
+const STRESS_COLUMNS: usize = 20;
+const STRESS_SPACING: f32 = 40.0;
+// Where the top left of the grid is.
+const STRESS_ORIGIN: Vec2 = Vec2::new(-380.0, 900.0);
+
+// A character spawned by the stress test. F12 fills the world with them, all firing
+// nonstop with infinite ammo to load the physics and projectile systems, and F12 again
+// removes them.
+#[derive(Component)]
+pub struct StressDummy;
+
+// The FPS and frame time, shown in the corner while the stress test is running.
+#[derive(Component)]
+struct FrameTimeReadout;
+
+fn spawn_frame_time_readout(mut commands: Commands) {
+    commands.spawn((
+        Text::default(),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.0),
+            left: Val::Px(10.0),
+            ..default()
+        },
+        FrameTimeReadout,
+    ));
+}
+
+fn toggle_stress_test(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    dummies: Query<Entity, With<StressDummy>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F12) {
+        return;
+    }
+    if !dummies.is_empty() {
+        info!("Stress test off");
+        for dummy in &dummies {
+            commands.entity(dummy).despawn_recursive();
+        }
+        return;
+    }
+    info!("Stress test on: spawning {STRESS_DUMMIES} characters");
+    for i in 0..STRESS_DUMMIES {
+        let column = (i % STRESS_COLUMNS) as f32;
+        let row = (i / STRESS_COLUMNS) as f32;
+        let entity = spawn_controllable_character(
+            &mut commands,
+            &SpawnConfig {
+                position: STRESS_ORIGIN + Vec2::new(column, -row) * STRESS_SPACING,
+                color: Color::srgb(0.4, 0.4, 0.4),
+                weapon: WeaponKind::Rifle,
+                invulnerability_secs: 0.0,
+                ..default()
+            },
+        );
+        commands.entity(entity).insert(StressDummy);
+    }
+}
+
+// Keeps every dummy spraying in random directions, with guns that never run dry.
+fn drive_stress_dummies(
+    mut rng: Local<InputRng>,
+    mut movement_event_writer: ActionWriter,
+    dummies: Query<(Entity, &Children), With<StressDummy>>,
+    mut guns: Query<(&mut Weapon, Option<&mut Heat>)>,
+) {
+    for (entity, children) in &dummies {
+        let aim = rng.direction();
+        movement_event_writer.send(PlayerAction::Aim(entity, aim.x, aim.y));
+        movement_event_writer.send(PlayerAction::Fire(entity, WeaponSlot::Primary, 1.0));
+        let mut guns = guns.iter_many_mut(children);
+        while let Some((mut weapon, heat)) = guns.fetch_next() {
+            weapon.ammo = Weapon::new(weapon.kind).ammo;
+            if let Some(mut heat) = heat {
+                heat.current = 0.0;
+                heat.overheated = false;
+            }
+        }
+    }
+}
+
+fn update_frame_time_readout(
+    diagnostics: Res<DiagnosticsStore>,
+    dummies: Query<(), With<StressDummy>>,
+    projectiles: Query<(), With<Projectile>>,
+    mut readouts: Query<&mut Text, With<FrameTimeReadout>>,
+) {
+    let text = if dummies.is_empty() {
+        String::new()
+    } else {
+        let fps = diagnostics
+            .get(&FrameTimeDiagnosticsPlugin::FPS)
+            .and_then(|fps| fps.smoothed())
+            .unwrap_or(0.0);
+        let frame_time = diagnostics
+            .get(&FrameTimeDiagnosticsPlugin::FRAME_TIME)
+            .and_then(|frame_time| frame_time.smoothed())
+            .unwrap_or(0.0);
+        format!(
+            "{fps:.0} FPS   {frame_time:.1} ms   {} characters   {} projectiles",
+            dummies.iter().count(),
+            projectiles.iter().count(),
+        )
+    };
+    for mut readout in &mut readouts {
+        if readout.0 != text {
+            readout.0.clone_from(&text);
+        }
+    }
+}
@@ -0,0 +1,40 @@
+// Helpers for tests that need the physics running.
+
+use std::time::Duration;
+
+use avian2d::{math::*, prelude::*};
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+
+// An app with just the physics and what it needs, with the uniform gravity off. Every
+// `update` runs exactly one fixed update at the default rate of 64 per second.
+pub fn physics_app() -> App {
+    physics_app_with(|_| {})
+}
+
+// Like `physics_app`, with `setup` adding plugins and resources before the first update.
+pub fn physics_app_with(setup: impl FnOnce(&mut App)) -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        TransformPlugin,
+        HierarchyPlugin,
+        AssetPlugin::default(),
+        bevy::scene::ScenePlugin,
+        PhysicsPlugins::default().with_length_unit(20.0),
+    ))
+    .init_resource::<Assets<Mesh>>()
+    .insert_resource(Gravity(Vector::ZERO))
+    .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+        1.0 / 64.0,
+    )));
+    setup(&mut app);
+    // Time doesn't advance on the very first update
+    app.update();
+    app
+}
+
+pub fn run_ticks(app: &mut App, ticks: usize) {
+    for _ in 0..ticks {
+        app.update();
+    }
+}
@@ -0,0 +1,116 @@
+use avian2d::prelude::*;
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+use crate::combat::{CombatSet, DamageEvent, Damageable};
+use crate::layers;
+
+pub struct TrainingPlugin;
+
+impl Plugin for TrainingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            record_dummy_damage
+                .after(CombatSet::Damage)
+                .before(CombatSet::Cleanup),
+        )
+        .add_systems(Update, (update_dummy_labels, reset_dummies).chain());
+    }
+}
+
+// The window over which a dummy reports the damage it has taken.
+const DAMAGE_WINDOW_SECS: f32 = 1.0;
+
+// A target practice dummy that never moves or dies and reports the damage it takes.
+#[derive(Component, Default)]
+pub struct Dummy {
+    // Hits taken within the damage window, as (time, amount).
+    recent: VecDeque<(f32, f32)>,
+    pub total: f32,
+}
+
+impl Dummy {
+    // Damage taken within the last `DAMAGE_WINDOW_SECS`.
+    pub fn recent_damage(&self) -> f32 {
+        self.recent.iter().map(|(_, amount)| amount).sum()
+    }
+}
+
+// Spawns a dummy standing at `position` with a label showing the damage it takes.
+pub fn spawn_dummy(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    position: Vec2,
+) -> Entity {
+    commands
+        .spawn((
+            Mesh2d(meshes.add(Capsule2d::new(12.5, 20.0))),
+            MeshMaterial2d(materials.add(Color::srgb(0.6, 0.6, 0.5))),
+            Transform::from_translation(position.extend(0.0)),
+            RigidBody::Static,
+            Collider::capsule(12.5, 20.0),
+            layers::player(),
+            Damageable::new(1000.0),
+            Dummy::default(),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text2d::new("0"),
+                TextFont {
+                    font_size: 14.0,
+                    ..default()
+                },
+                Transform::from_xyz(0.0, 50.0, 1.0),
+            ));
+        })
+        .id()
+}
+
+fn record_dummy_damage(
+    time: Res<Time>,
+    mut damage_event_reader: EventReader<DamageEvent>,
+    mut dummies: Query<(&mut Dummy, &mut Damageable)>,
+) {
+    let now = time.elapsed_secs();
+    for damage in damage_event_reader.read() {
+        if let Ok((mut dummy, mut damageable)) = dummies.get_mut(damage.target) {
+            dummy.recent.push_back((now, damage.total()));
+            dummy.total += damage.total();
+            // Dummies shrug off every hit
+            damageable.health = damageable.max;
+        }
+    }
+    for (mut dummy, _) in &mut dummies {
+        while dummy
+            .recent
+            .front()
+            .is_some_and(|(at, _)| now - at > DAMAGE_WINDOW_SECS)
+        {
+            dummy.recent.pop_front();
+        }
+    }
+}
+
+fn update_dummy_labels(dummies: Query<(&Dummy, &Children)>, mut labels: Query<&mut Text2d>) {
+    for (dummy, children) in &dummies {
+        for child in children.iter() {
+            if let Ok(mut label) = labels.get_mut(*child) {
+                label.0 = format!("{:.0} / s\n{:.0} total", dummy.recent_damage(), dummy.total);
+            }
+        }
+    }
+}
+
+// Clears the damage counters of every dummy when F3 is pressed.
+fn reset_dummies(keyboard_input: Res<ButtonInput<KeyCode>>, mut dummies: Query<&mut Dummy>) {
+    if !keyboard_input.just_pressed(KeyCode::F3) {
+        return;
+    }
+    for mut dummy in &mut dummies {
+        info!("Dummy took {:.0} damage in total", dummy.total);
+        dummy.recent.clear();
+        dummy.total = 0.0;
+    }
+}
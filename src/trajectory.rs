@@ -0,0 +1,90 @@
+use avian2d::prelude::*;
+use bevy::prelude::*;
+
+use crate::gravity::Planet;
+use crate::player::{AimRotation, PlayerAction, SmoothedAim};
+use crate::weapons::{Gun, GunVisual, Weapon};
+
+pub struct TrajectoryPlugin;
+
+impl Plugin for TrajectoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (toggle_trajectory_preview, draw_trajectory_previews).chain());
+    }
+}
+
+// How far ahead the preview simulates a shot, in steps of `PREVIEW_STEP_SECS`.
+const PREVIEW_STEPS: usize = 60;
+const PREVIEW_STEP_SECS: f32 = 1.0 / 30.0;
+
+// Draws where a character's next shot would go, for weapons whose projectiles fall.
+#[derive(Component)]
+pub struct TrajectoryPreview {
+    pub enabled: bool,
+}
+
+impl Default for TrajectoryPreview {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+fn toggle_trajectory_preview(
+    mut movement_event_reader: EventReader<PlayerAction>,
+    mut previews: Query<&mut TrajectoryPreview>,
+) {
+    for event in movement_event_reader.read() {
+        if let PlayerAction::TogglePreview(character) = event {
+            if let Ok(mut preview) = previews.get_mut(*character) {
+                preview.enabled = !preview.enabled;
+            }
+        }
+    }
+}
+
+// Steps a projectile forward under the pull of the planets and the uniform world gravity,
+// the same way the real one moves, and marks its path.
+fn draw_trajectory_previews(
+    guns: Query<(&Parent, &Weapon, Option<&GunVisual>), With<Gun>>,
+    characters: Query<(&Transform, &AimRotation, Option<&SmoothedAim>, &TrajectoryPreview)>,
+    planets: Query<(&Planet, &Position)>,
+    world_gravity: Res<Gravity>,
+    mut gizmos: Gizmos,
+) {
+    for (parent, weapon, visual) in &guns {
+        let gravity_scale = weapon.kind.gravity_scale();
+        if gravity_scale <= 0.0 {
+            continue;
+        }
+        let Ok((transform, aim, smoothed, preview)) = characters.get(parent.get()) else {
+            continue;
+        };
+        if !preview.enabled {
+            continue;
+        }
+        // Guns point down their local Y axis
+        let aim = smoothed.map_or(aim.0, |smoothed| smoothed.rotation);
+        let direction = (aim * Vec3::NEG_Y).truncate();
+        let muzzle_distance = weapon.muzzle_distance(visual);
+        let mut position = transform.translation.truncate() + direction * muzzle_distance;
+        let mut velocity = direction * weapon.muzzle_velocity;
+        let steps = PREVIEW_STEPS.min((weapon.projectile_lifetime / PREVIEW_STEP_SECS) as usize);
+        for step in 0..steps {
+            let gravity = planets.iter().fold(world_gravity.0, |acc, (planet, center)| {
+                acc + planet.gravity_at(center.0, position)
+            });
+            velocity += gravity * gravity_scale * PREVIEW_STEP_SECS;
+            position += velocity * PREVIEW_STEP_SECS;
+            // Stop at the ground
+            if planets
+                .iter()
+                .any(|(planet, center)| position.distance(center.0) <= planet.radius)
+            {
+                break;
+            }
+            if step % 2 == 0 {
+                gizmos.circle_2d(position, 2.0, weapon.projectile_color);
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use avian2d::prelude::*;
+use bevy::prelude::*;
+use std::collections::HashSet;
+
+pub struct TriggerPlugin;
+
+impl Plugin for TriggerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ZoneEntered>()
+            .add_event::<ZoneExited>()
+            .add_systems(Update, track_zone_occupants);
+    }
+}
+
+// An area that keeps track of the bodies inside it, using its collider as a sensor.
+// Anything that reacts to bodies being in an area (force zones, pickups, kill zones, ...)
+// can build on it instead of checking overlaps itself.
+#[derive(Component, Default)]
+#[require(Sensor, RigidBody(|| RigidBody::Static))]
+pub struct TriggerZone {
+    // The bodies currently overlapping the zone.
+    pub occupants: HashSet<Entity>,
+}
+
+// Sent when a body starts overlapping a `TriggerZone`, as (zone, body).
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ZoneEntered(pub Entity, pub Entity);
+
+// Sent when a body stops overlapping a `TriggerZone` or is despawned inside it, as (zone, body).
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ZoneExited(pub Entity, pub Entity);
+
+fn track_zone_occupants(
+    mut started_reader: EventReader<CollisionStarted>,
+    mut ended_reader: EventReader<CollisionEnded>,
+    mut entered_writer: EventWriter<ZoneEntered>,
+    mut exited_writer: EventWriter<ZoneExited>,
+    mut zones: Query<(Entity, &mut TriggerZone)>,
+    mut removed: RemovedComponents<Collider>,
+) {
+    for CollisionStarted(entity1, entity2) in started_reader.read() {
+        for (zone, body) in [(*entity1, *entity2), (*entity2, *entity1)] {
+            if let Ok((_, mut trigger)) = zones.get_mut(zone) {
+                if trigger.occupants.insert(body) {
+                    entered_writer.send(ZoneEntered(zone, body));
+                }
+            }
+        }
+    }
+    for CollisionEnded(entity1, entity2) in ended_reader.read() {
+        for (zone, body) in [(*entity1, *entity2), (*entity2, *entity1)] {
+            if let Ok((_, mut trigger)) = zones.get_mut(zone) {
+                if trigger.occupants.remove(&body) {
+                    exited_writer.send(ZoneExited(zone, body));
+                }
+            }
+        }
+    }
+    // Despawned bodies don't always end their collisions
+    for body in removed.read() {
+        for (zone, mut trigger) in &mut zones {
+            if trigger.occupants.remove(&body) {
+                exited_writer.send(ZoneExited(zone, body));
+            }
+        }
+    }
+}
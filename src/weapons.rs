@@ -1,10 +1,1085 @@
-use bevy::prelude::*;
+use avian2d::prelude::*;
+use bevy::{prelude::*, sprite::Anchor};
+use std::collections::VecDeque;
+use std::f32::consts::{PI, TAU};
+
+use crate::layers;
+use crate::player::FireImpulse;
+use crate::rng::GameRng;
 
 #[derive(Component)]
 pub struct Gun;
 
+// Which of a character's guns an action is meant for. Each slot has its own trigger.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "net", derive(serde::Serialize, serde::Deserialize))]
+pub enum WeaponSlot {
+    #[default]
+    Primary,
+    Secondary,
+}
+
+impl WeaponSlot {
+    pub const ALL: [WeaponSlot; 2] = [WeaponSlot::Primary, WeaponSlot::Secondary];
+
+    pub fn index(&self) -> usize {
+        match self {
+            WeaponSlot::Primary => 0,
+            WeaponSlot::Secondary => 1,
+        }
+    }
+}
+
+// The guns a character is holding. A character with a `secondary` gun dual wields,
+// firing each gun with its own trigger, with their own cooldown and ammo.
+#[derive(Component)]
+pub struct Loadout {
+    pub primary: Entity,
+    pub secondary: Option<Entity>,
+}
+
+impl Loadout {
+    // Which slot `gun` is held in, if the character is holding it at all.
+    pub fn slot_of(&self, gun: Entity) -> Option<WeaponSlot> {
+        if gun == self.primary {
+            Some(WeaponSlot::Primary)
+        } else if Some(gun) == self.secondary {
+            Some(WeaponSlot::Secondary)
+        } else {
+            None
+        }
+    }
+}
+
+// The kind of weapon a gun is, which determines the stats of the projectiles it fires.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WeaponKind {
+    Pistol,
+    Rifle,
+    Grenade,
+    // Fires slow, long-lived rounds that fall under planetary gravity. Fired tangentially
+    // near a small planet they go into orbit and can come around to hit the far side.
+    Orbiter,
+    // Lobs a shell that bursts into a fan of fragments partway through its flight.
+    Cluster,
+    // Lays mines that stick to the ground and go off when their owner detonates them.
+    Mine,
+}
+
+impl WeaponKind {
+    // Damage dealt by a hit within the full damage range.
+    pub fn damage(&self) -> f32 {
+        match self {
+            WeaponKind::Pistol => 20.0,
+            WeaponKind::Rifle => 35.0,
+            WeaponKind::Grenade => 50.0,
+            WeaponKind::Orbiter => 25.0,
+            WeaponKind::Cluster => 40.0,
+            WeaponKind::Mine => 60.0,
+        }
+    }
+
+    // How strongly gravity pulls on the projectiles. Bullets fly straight,
+    // lobbed weapons arc.
+    pub fn gravity_scale(&self) -> f32 {
+        match self {
+            WeaponKind::Pistol | WeaponKind::Rifle => 0.0,
+            WeaponKind::Grenade
+            | WeaponKind::Orbiter
+            | WeaponKind::Cluster
+            | WeaponKind::Mine => 1.0,
+        }
+    }
+
+    // How hard the weapon kicks when fired, from 0 to 1.
+    pub fn recoil(&self) -> f32 {
+        match self {
+            WeaponKind::Pistol => 0.3,
+            WeaponKind::Rifle => 0.6,
+            WeaponKind::Grenade => 0.5,
+            WeaponKind::Orbiter => 0.2,
+            WeaponKind::Cluster => 0.4,
+            WeaponKind::Mine => 0.2,
+        }
+    }
+
+    // Radius of the explosion the projectiles set off on impact, if they explode at all.
+    pub fn blast_radius(&self) -> Option<f32> {
+        match self {
+            WeaponKind::Grenade => Some(100.0),
+            WeaponKind::Mine => Some(110.0),
+            _ => None,
+        }
+    }
+
+    // How the projectiles burst apart in flight, if they do.
+    pub fn splitting(&self) -> Option<Splitting> {
+        match self {
+            WeaponKind::Cluster => Some(Splitting::new(0.5, 6, f32::to_radians(40.0))),
+            _ => None,
+        }
+    }
+
+    // Whether the projectiles stick to the ground as `Sticky` mines instead of hitting
+    // what they touch.
+    pub fn sticky(&self) -> bool {
+        *self == WeaponKind::Mine
+    }
+
+    pub fn falloff(&self) -> DamageFalloff {
+        match self {
+            WeaponKind::Pistol => DamageFalloff {
+                full_damage_range: 150.0,
+                min_damage_range: 600.0,
+                min_multiplier: 0.4,
+            },
+            WeaponKind::Rifle => DamageFalloff {
+                full_damage_range: 400.0,
+                min_damage_range: 1200.0,
+                min_multiplier: 0.6,
+            },
+            // Lobbed, orbiting and laid projectiles hit just as hard wherever they land
+            WeaponKind::Grenade
+            | WeaponKind::Orbiter
+            | WeaponKind::Cluster
+            | WeaponKind::Mine => DamageFalloff {
+                full_damage_range: f32::MAX,
+                min_damage_range: f32::MAX,
+                min_multiplier: 1.0,
+            },
+        }
+    }
+}
+
+// How far from the shooter's center projectiles spawn by default, just clear of the
+// default character capsule (12.5 radius plus 10 of half length) in any direction.
+// Characters of other sizes get theirs from `CharacterSize::muzzle_offset`.
+pub const DEFAULT_MUZZLE_OFFSET: f32 = 25.0;
+
+const BASH_COOLDOWN_SECS: f32 = 0.5;
+
+// The stats of the weapon a `Gun` fires with.
+#[derive(Component, Clone)]
+pub struct Weapon {
+    pub kind: WeaponKind,
+    pub damage: f32,
+    pub muzzle_velocity: f32,
+    // Time between shots. The weapon can fire once the timer has finished.
+    pub cooldown: Timer,
+    pub projectile_lifetime: f32,
+    pub projectile_size: Vec2,
+    pub projectile_color: Color,
+    // Heavier projectiles shove what they hit harder at the same speed.
+    pub projectile_mass: f32,
+    // How far along the aim from the shooter's center projectiles spawn. The projectile's
+    // own size is added on top, so it never starts out overlapping the shooter.
+    pub muzzle_offset: f32,
+    // Remaining shots, or `None` for unlimited ammo.
+    pub ammo: Option<u32>,
+    // Time between swings of the gun once it's out of ammo.
+    pub bash_cooldown: Timer,
+}
+
+impl Weapon {
+    pub fn new(kind: WeaponKind) -> Self {
+        let (muzzle_velocity, cooldown, projectile_lifetime, ammo) = match kind {
+            WeaponKind::Pistol => (500.0, 0.25, 3.0, None),
+            WeaponKind::Rifle => (900.0, 0.1, 4.0, Some(30)),
+            WeaponKind::Grenade => (400.0, 1.0, 5.0, Some(5)),
+            // A circular orbit needs a speed of sqrt(g * r), about 630 around the moon
+            WeaponKind::Orbiter => (650.0, 0.5, 10.0, Some(10)),
+            WeaponKind::Cluster => (550.0, 0.8, 4.0, Some(6)),
+            WeaponKind::Mine => (350.0, 0.6, 6.0, Some(3)),
+        };
+        let (projectile_size, projectile_color, projectile_mass) = match kind {
+            WeaponKind::Pistol => (Vec2::splat(12.0), Color::WHITE, 6.0),
+            // Light and fast, so a burst doesn't send crates flying
+            WeaponKind::Rifle => (Vec2::new(6.0, 14.0), Color::srgb(1.0, 0.9, 0.2), 3.0),
+            WeaponKind::Grenade => (Vec2::splat(22.0), Color::srgb(1.0, 0.5, 0.1), 30.0),
+            WeaponKind::Orbiter => (Vec2::splat(10.0), Color::srgb(0.3, 0.9, 1.0), 10.0),
+            WeaponKind::Cluster => (Vec2::splat(16.0), Color::srgb(0.8, 0.4, 1.0), 12.0),
+            WeaponKind::Mine => (Vec2::new(16.0, 8.0), Color::srgb(1.0, 0.2, 0.3), 8.0),
+        };
+        let mut cooldown = Timer::from_seconds(cooldown, TimerMode::Once);
+        // Start out ready to fire
+        cooldown.tick(cooldown.duration());
+        let mut bash_cooldown = Timer::from_seconds(BASH_COOLDOWN_SECS, TimerMode::Once);
+        bash_cooldown.tick(bash_cooldown.duration());
+        Self {
+            kind,
+            damage: kind.damage(),
+            muzzle_velocity,
+            cooldown,
+            projectile_lifetime,
+            projectile_size,
+            projectile_color,
+            projectile_mass,
+            muzzle_offset: DEFAULT_MUZZLE_OFFSET,
+            ammo,
+            bash_cooldown,
+        }
+    }
+
+    pub fn can_fire(&self) -> bool {
+        self.cooldown.finished() && self.ammo != Some(0)
+    }
+
+    // How far from the shooter's center projectiles spawn: at the tip of the barrel,
+    // but never inside the shooter, and far enough out to fit the whole projectile.
+    pub fn muzzle_distance(&self, visual: Option<&GunVisual>) -> f32 {
+        let tip = visual.map_or(0.0, GunVisual::tip);
+        self.muzzle_offset.max(tip) + self.projectile_size.max_element() / 2.0
+    }
+
+    pub fn out_of_ammo(&self) -> bool {
+        self.ammo == Some(0)
+    }
+
+    // Whether the empty gun can be swung again. See `MatchConfig::gun_bash`.
+    pub fn can_bash(&self) -> bool {
+        self.out_of_ammo() && self.bash_cooldown.finished()
+    }
+
+    // Starts the cooldown and uses up a round of ammo.
+    pub fn consume_shot(&mut self) {
+        self.cooldown.reset();
+        if let Some(ammo) = self.ammo.as_mut() {
+            *ammo = ammo.saturating_sub(1);
+        }
+    }
+}
+
+// What a gun looks like: a barrel `width` wide and `length` long, starting
+// `pivot_offset` from the shooter's center and pointing along the aim.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct GunVisual {
+    pub length: f32,
+    pub width: f32,
+    pub pivot_offset: f32,
+}
+
+impl GunVisual {
+    pub fn new(kind: WeaponKind) -> Self {
+        let (length, width) = match kind {
+            WeaponKind::Pistol => (30.0, 10.0),
+            WeaponKind::Rifle => (48.0, 6.0),
+            WeaponKind::Grenade => (28.0, 14.0),
+            WeaponKind::Orbiter => (40.0, 8.0),
+            WeaponKind::Cluster => (34.0, 12.0),
+            WeaponKind::Mine => (26.0, 12.0),
+        };
+        Self {
+            length,
+            width,
+            pivot_offset: 0.0,
+        }
+    }
+
+    // Distance from the shooter's center to the end of the barrel.
+    pub fn tip(&self) -> f32 {
+        self.pivot_offset + self.length
+    }
+
+    // A sprite of the barrel. Guns point down their local Y axis, so the sprite hangs
+    // from its top edge, pushed out by the pivot offset.
+    pub fn sprite(&self, color: Color) -> Sprite {
+        Sprite {
+            color,
+            custom_size: Some(Vec2::new(self.width, self.length)),
+            anchor: Anchor::Custom(Vec2::new(0.0, 0.5 + self.pivot_offset / self.length)),
+            ..default()
+        }
+    }
+}
+
+// Limits where a gun can point, e.g. for turrets or stances that can't aim behind them.
+// Angles are in radians, counterclockwise from the world's +X axis, with `min_angle`
+// to `max_angle` going counterclockwise being the allowed range. The default allows
+// every direction.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct AimConstraint {
+    pub min_angle: f32,
+    pub max_angle: f32,
+}
+
+impl Default for AimConstraint {
+    fn default() -> Self {
+        Self {
+            min_angle: -PI,
+            max_angle: PI,
+        }
+    }
+}
+
+// How much closer an aim outside the range has to be to the other end of it to switch
+// ends, in radians. Without this, aiming straight into the forbidden range would make
+// the gun flicker between both ends as the stick wobbles.
+const AIM_CONSTRAINT_HYSTERESIS: f32 = 0.2;
+
+// The absolute difference between two angles, going the short way around.
+fn angle_between(a: f32, b: f32) -> f32 {
+    let difference = (a - b).rem_euclid(TAU);
+    difference.min(TAU - difference)
+}
+
+impl AimConstraint {
+    pub fn contains(&self, angle: f32) -> bool {
+        // The ends are a full turn or more apart, so every direction is allowed
+        if self.max_angle - self.min_angle >= TAU {
+            return true;
+        }
+        let span = (self.max_angle - self.min_angle).rem_euclid(TAU);
+        (angle - self.min_angle).rem_euclid(TAU) <= span
+    }
+
+    // Clamps `angle` into the allowed range. Angles outside it go to the nearest end,
+    // preferring the end `previous` is already at.
+    pub fn clamp(&self, angle: f32, previous: f32) -> f32 {
+        if self.contains(angle) {
+            return angle;
+        }
+        let (current_end, other_end) =
+            if angle_between(previous, self.min_angle) <= angle_between(previous, self.max_angle) {
+                (self.min_angle, self.max_angle)
+            } else {
+                (self.max_angle, self.min_angle)
+            };
+        if angle_between(angle, other_end) + AIM_CONSTRAINT_HYSTERESIS
+            < angle_between(angle, current_end)
+        {
+            other_end
+        } else {
+            current_end
+        }
+    }
+}
+
+// How far shots stray from the aim, in radians. Every shot blooms the spread up to `max`,
+// and it recovers back down to `base` over time, so holding the trigger costs accuracy.
+// Shots fired in the air stray `airborne_multiplier` times as far, rewarding players who
+// jump to line up a shot.
+#[derive(Component, Clone)]
+pub struct Spread {
+    pub base: f32,
+    pub max: f32,
+    pub bloom_per_shot: f32,
+    // Radians per second.
+    pub recovery: f32,
+    pub current: f32,
+    pub airborne_multiplier: f32,
+}
+
+impl Spread {
+    pub fn new(kind: WeaponKind) -> Self {
+        let (base, max, bloom_per_shot, recovery, airborne_multiplier) = match kind {
+            WeaponKind::Pistol => (2.0, 5.0, 1.5, 8.0, 0.5),
+            WeaponKind::Rifle => (1.5, 10.0, 1.0, 6.0, 0.4),
+            WeaponKind::Grenade
+            | WeaponKind::Orbiter
+            | WeaponKind::Cluster
+            | WeaponKind::Mine => (0.0, 0.0, 0.0, 0.0, 1.0),
+        };
+        Self {
+            base: f32::to_radians(base),
+            max: f32::to_radians(max),
+            bloom_per_shot: f32::to_radians(bloom_per_shot),
+            recovery: f32::to_radians(recovery),
+            current: f32::to_radians(base),
+            airborne_multiplier,
+        }
+    }
+
+    // The current spread for a shooter standing on the ground or not.
+    pub fn current_for(&self, grounded: bool) -> f32 {
+        if grounded {
+            self.current
+        } else {
+            self.current * self.airborne_multiplier
+        }
+    }
+
+    // A random deviation within the current spread, in radians.
+    pub fn random_angle(&self, rng: &mut GameRng, grounded: bool) -> f32 {
+        let spread = self.current_for(grounded);
+        rng.float(-spread..spread)
+    }
+
+    // Widens the spread after a shot.
+    pub fn bloom(&mut self) {
+        self.current = (self.current + self.bloom_per_shot).min(self.max);
+    }
+}
+
+// The trigger of every character holding a gun, and which gun is in which slot.
+type Triggers<'w, 's> = Query<'w, 's, (&'static FireImpulse, Option<&'static Loadout>)>;
+
+// Whether `gun` is under sustained fire: it fired too recently to fire again, or its
+// trigger is held and it's ready to fire.
+fn firing(gun: Entity, parent: &Parent, weapon: &Weapon, triggers: &Triggers) -> bool {
+    if !weapon.cooldown.finished() {
+        return true;
+    }
+    let Ok((fire, loadout)) = triggers.get(parent.get()) else {
+        return false;
+    };
+    // Characters without a loadout only have the one gun
+    let slot = loadout.map_or(Some(WeaponSlot::Primary), |loadout| loadout.slot_of(gun));
+    slot.is_some_and(|slot| fire.get(slot) > 0.0) && weapon.can_fire()
+}
+
+// Lets the spread of every gun settle back towards its base, once it stops firing.
+pub fn recover_spread(
+    time: Res<Time>,
+    triggers: Triggers,
+    mut spreads: Query<(Entity, &Parent, &Weapon, &mut Spread)>,
+) {
+    for (gun, parent, weapon, mut spread) in &mut spreads {
+        if firing(gun, parent, weapon, &triggers) {
+            continue;
+        }
+        let recovered = spread.current - spread.recovery * time.delta_secs();
+        spread.current = recovered.max(spread.base);
+    }
+}
+
+// How hot a gun's barrel may get before it overheats, for guns that overheat instead of
+// running out of ammo. Every shot adds `per_shot` and the barrel cools by `cooldown_rate`
+// per second. Once `current` reaches `max` the gun is `overheated` and won't fire until it
+// has cooled below `HEAT_RESUME_FRACTION` of `max`.
+#[derive(Component, Clone, Debug)]
+pub struct Heat {
+    pub current: f32,
+    pub max: f32,
+    pub per_shot: f32,
+    pub cooldown_rate: f32,
+    pub overheated: bool,
+}
+
+// The fraction of `Heat::max` an overheated gun must cool below to fire again.
+pub const HEAT_RESUME_FRACTION: f32 = 0.3;
+
+impl Heat {
+    // The heat model of a weapon, if it overheats at all.
+    pub fn new(kind: WeaponKind) -> Option<Self> {
+        let (max, per_shot, cooldown_rate) = match kind {
+            // The pistol never runs out of ammo, so keep it from firing forever
+            WeaponKind::Pistol => (100.0, 14.0, 35.0),
+            _ => return None,
+        };
+        Some(Self {
+            current: 0.0,
+            max,
+            per_shot,
+            cooldown_rate,
+            overheated: false,
+        })
+    }
+
+    pub fn can_fire(&self) -> bool {
+        !self.overheated
+    }
+
+    pub fn add_shot(&mut self) {
+        self.current = (self.current + self.per_shot).min(self.max);
+        if self.current >= self.max {
+            self.overheated = true;
+        }
+    }
+
+    pub fn cool(&mut self, secs: f32) {
+        self.current = (self.current - self.cooldown_rate * secs).max(0.0);
+        if self.overheated && self.current < self.max * HEAT_RESUME_FRACTION {
+            self.overheated = false;
+        }
+    }
+
+    // How hot the barrel is, from 0 to 1.
+    pub fn fraction(&self) -> f32 {
+        self.current / self.max
+    }
+}
+
+// How hard firing shoves the shooter back, opposite the shot, as a change in velocity.
+// Shooters on the ground only get `SELF_IMPULSE_GROUNDED_SCALE` of it, so in the air a
+// heavy weapon fired at the ground doubles as an extra jump.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct SelfImpulse(pub f32);
+
+pub const SELF_IMPULSE_GROUNDED_SCALE: f32 = 0.25;
+
+impl SelfImpulse {
+    // The shove of a weapon, if it has one worth feeling.
+    pub fn new(kind: WeaponKind) -> Option<Self> {
+        match kind {
+            WeaponKind::Grenade => Some(Self(650.0)),
+            WeaponKind::Orbiter => Some(Self(150.0)),
+            WeaponKind::Pistol
+            | WeaponKind::Rifle
+            | WeaponKind::Cluster
+            | WeaponKind::Mine => None,
+        }
+    }
+
+    // The shove for a shooter that is or isn't standing on the ground.
+    pub fn for_shooter(&self, grounded: bool) -> f32 {
+        if grounded {
+            self.0 * SELF_IMPULSE_GROUNDED_SCALE
+        } else {
+            self.0
+        }
+    }
+}
+
+// The color of a gun with a cool barrel. Hot guns are tinted towards red from it.
+#[derive(Component, Clone, Copy)]
+pub struct GunColor(pub Color);
+
+const OVERHEATED_COLOR: Color = Color::srgb(1.0, 0.15, 0.0);
+
+// Cools the barrel of every gun that isn't firing and tints it by how hot it is.
+// Overheated guns can't fire, so they cool down even with the trigger held.
+pub fn cool_guns(
+    time: Res<Time>,
+    triggers: Triggers,
+    mut guns: Query<(Entity, &Parent, &Weapon, &mut Heat, Option<&GunColor>, &mut Sprite)>,
+) {
+    for (gun, parent, weapon, mut heat, color, mut sprite) in &mut guns {
+        if heat.overheated || !firing(gun, parent, weapon, &triggers) {
+            heat.cool(time.delta_secs());
+        }
+        if let Some(color) = color {
+            sprite.color = color.0.mix(&OVERHEATED_COLOR, heat.fraction());
+        }
+    }
+}
+
+// How damage decreases with the distance a projectile has travelled.
+// Hits closer than `full_damage_range` deal full damage, hits further than
+// `min_damage_range` deal `min_multiplier` of it, and anything in between is
+// interpolated linearly.
+#[derive(Clone, Copy, Debug)]
+pub struct DamageFalloff {
+    pub full_damage_range: f32,
+    pub min_damage_range: f32,
+    pub min_multiplier: f32,
+}
+
+impl DamageFalloff {
+    pub fn multiplier(&self, distance: f32) -> f32 {
+        if distance <= self.full_damage_range {
+            1.0
+        } else if distance >= self.min_damage_range {
+            self.min_multiplier
+        } else {
+            let t = (distance - self.full_damage_range)
+                / (self.min_damage_range - self.full_damage_range);
+            1.0 + (self.min_multiplier - 1.0) * t
+        }
+    }
+}
+
+// An event sent whenever a character fires a projectile.
+#[derive(Event)]
+pub struct ShotFired {
+    pub shooter: Entity,
+    pub weapon: WeaponKind,
+}
+
 #[derive(Component)]
 pub struct Projectile {
-    pub velocity: Vec2,
     pub lifetime: f32, // Time before the projectile is destroyed
-}
\ No newline at end of file
+    pub max_lifetime: f32, // The lifetime the projectile started out with
+    pub owner: Entity,
+    pub spawn_position: Vec2,
+    pub damage: f32,
+    pub falloff: DamageFalloff,
+    pub blast_radius: Option<f32>,
+}
+
+impl Projectile {
+    // The damage this projectile deals when hitting something at `position`.
+    pub fn damage_at(&self, position: Vec2) -> f32 {
+        self.damage * self.falloff.multiplier(position.distance(self.spawn_position))
+    }
+
+    // How much of its lifetime the projectile has left, from 1 when fired down to 0.
+    pub fn life_fraction(&self) -> f32 {
+        (self.lifetime / self.max_lifetime).clamp(0.0, 1.0)
+    }
+}
+
+// Projectiles faster than this use continuous collision detection.
+pub const CCD_SPEED_THRESHOLD: f32 = 600.0;
+
+// Everything that sets one shot apart from another.
+pub struct Shot {
+    pub owner: Entity,
+    pub transform: Transform,
+    pub velocity: Vec2,
+    // How long the projectile lives, in seconds.
+    pub lifetime: f32,
+    pub damage: f32,
+    pub falloff: DamageFalloff,
+    pub blast_radius: Option<f32>,
+    pub color: Color,
+    pub size: Vec2,
+    pub collider: Collider,
+    pub mass: f32,
+    pub gravity_scale: f32,
+}
+
+// Puts `entity` in the state of a freshly fired `shot`. Every component a projectile
+// carries is set again, and the ones it can pick up along the way, like being stuck as a
+// mine or evicted by age, are removed. Every projectile is set up through here, so that
+// an entity used for one before can't carry stale state into its next shot.
+pub fn reset_projectile(entity: &mut EntityCommands, shot: Shot) {
+    entity
+        .remove::<(Splitting, Sticky, Sensor, SweptCcd, ProjectileSerial)>()
+        .insert((
+            Projectile {
+                lifetime: shot.lifetime,
+                max_lifetime: shot.lifetime,
+                owner: shot.owner,
+                spawn_position: shot.transform.translation.truncate(),
+                damage: shot.damage,
+                falloff: shot.falloff,
+                blast_radius: shot.blast_radius,
+            },
+            Sprite {
+                color: shot.color.with_alpha(1.0),
+                custom_size: Some(shot.size),
+                ..default()
+            },
+            shot.transform,
+            Visibility::Inherited,
+            RigidBody::Dynamic,
+            shot.collider,
+            layers::projectile(),
+            Mass(shot.mass),
+            GravityScale(shot.gravity_scale),
+            LinearVelocity(shot.velocity),
+            AngularVelocity::ZERO,
+        ));
+    // Fast projectiles could skip past thin colliders between physics steps
+    if shot.velocity.length() > CCD_SPEED_THRESHOLD {
+        entity.insert(SweptCcd::default());
+    }
+}
+
+// The most projectiles allowed in flight at once, across every shooter. Past it the
+// oldest ones are removed first, to keep frame times in check when everyone fires at once.
+#[derive(Resource)]
+pub struct ProjectileBudget(pub usize);
+
+impl Default for ProjectileBudget {
+    fn default() -> Self {
+        Self(512)
+    }
+}
+
+// When a projectile was fired relative to the others, for evicting the oldest first.
+#[derive(Component)]
+pub struct ProjectileSerial(u64);
+
+pub fn enforce_projectile_budget(
+    mut commands: Commands,
+    budget: Res<ProjectileBudget>,
+    mut next_serial: Local<u64>,
+    mut evicting: Local<bool>,
+    projectiles: Query<(Entity, Option<&ProjectileSerial>), With<Projectile>>,
+) {
+    let mut live: Vec<(u64, Entity)> = projectiles
+        .iter()
+        .map(|(entity, serial)| {
+            let serial = serial.map_or_else(
+                || {
+                    let serial = *next_serial;
+                    *next_serial += 1;
+                    commands.entity(entity).insert(ProjectileSerial(serial));
+                    serial
+                },
+                |serial| serial.0,
+            );
+            (serial, entity)
+        })
+        .collect();
+    let excess = live.len().saturating_sub(budget.0);
+    // Only log when eviction starts, not on every frame it goes on for
+    if excess > 0 && !*evicting {
+        warn!(
+            "Over the projectile budget of {}, removing the oldest projectiles",
+            budget.0
+        );
+    }
+    *evicting = excess > 0;
+    if excess == 0 {
+        return;
+    }
+    live.sort_unstable_by_key(|(serial, _)| *serial);
+    for (_, entity) in live.into_iter().take(excess) {
+        commands.entity(entity).despawn();
+    }
+}
+
+// A projectile that bursts into `fragments` smaller ones once `at_time` runs out. The
+// fragments fan out over `spread` radians around its velocity at that point.
+#[derive(Component, Clone)]
+pub struct Splitting {
+    pub at_time: Timer,
+    pub fragments: u32,
+    pub spread: f32,
+}
+
+impl Splitting {
+    pub fn new(secs: f32, fragments: u32, spread: f32) -> Self {
+        Self {
+            at_time: Timer::from_seconds(secs, TimerMode::Once),
+            fragments,
+            spread,
+        }
+    }
+}
+
+// How much of the shell's damage each fragment deals.
+const FRAGMENT_DAMAGE_SCALE: f32 = 0.35;
+const FRAGMENT_SIZE: f32 = 6.0;
+const FRAGMENT_MASS: f32 = 2.0;
+
+// Replaces splitting projectiles whose time has come with their fragments. The fragments
+// carry on from the shell's position and speed, and still count as the owner's shots.
+pub fn split_projectiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut shells: Query<(
+        Entity,
+        &mut Splitting,
+        &Projectile,
+        &Transform,
+        &LinearVelocity,
+        &Sprite,
+        &GravityScale,
+    )>,
+) {
+    for (entity, mut splitting, projectile, transform, velocity, sprite, gravity_scale) in
+        &mut shells
+    {
+        if !splitting.at_time.tick(time.delta()).finished() {
+            continue;
+        }
+        let count = splitting.fragments.max(1);
+        for i in 0..count {
+            // Evenly across the spread, centered on the shell's heading
+            let t = if count == 1 {
+                0.5
+            } else {
+                i as f32 / (count - 1) as f32
+            };
+            let angle = (t - 0.5) * splitting.spread;
+            let fragment_velocity = Vec2::from_angle(angle).rotate(velocity.0);
+            // Fragments only live out what's left of the shell's lifetime
+            reset_projectile(
+                &mut commands.spawn_empty(),
+                Shot {
+                    owner: projectile.owner,
+                    transform: Transform::from_translation(transform.translation),
+                    velocity: fragment_velocity,
+                    lifetime: projectile.lifetime,
+                    damage: projectile.damage * FRAGMENT_DAMAGE_SCALE,
+                    falloff: projectile.falloff,
+                    blast_radius: None,
+                    color: sprite.color,
+                    size: Vec2::splat(FRAGMENT_SIZE),
+                    collider: Collider::circle(FRAGMENT_SIZE / 2.0),
+                    mass: FRAGMENT_MASS,
+                    gravity_scale: gravity_scale.0,
+                },
+            );
+        }
+        commands.entity(entity).despawn();
+    }
+}
+
+// A mine that sticks to the first piece of ground it touches and then waits, `armed`,
+// until its owner sends `PlayerAction::Detonate`.
+#[derive(Component, Default)]
+pub struct Sticky {
+    pub armed: bool,
+}
+
+// The armed mines a character has laid, oldest first. Laying more than `MAX_PLACED_MINES`
+// removes the oldest.
+#[derive(Component, Default)]
+pub struct PlacedMines(pub VecDeque<Entity>);
+
+pub const MAX_PLACED_MINES: usize = 5;
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::testing::{physics_app, run_ticks};
+
+    fn shot(owner: Entity, speed: f32) -> Shot {
+        Shot {
+            owner,
+            transform: Transform::from_xyz(10.0, 20.0, 0.0),
+            velocity: Vec2::new(speed, 0.0),
+            lifetime: 2.0,
+            damage: 10.0,
+            falloff: WeaponKind::Pistol.falloff(),
+            blast_radius: None,
+            color: Color::srgb(1.0, 0.5, 0.0),
+            size: Vec2::splat(4.0),
+            collider: Collider::circle(2.0),
+            mass: 1.0,
+            gravity_scale: 1.0,
+        }
+    }
+
+    fn reset(world: &mut World, entity: Entity, shot: Shot) {
+        world
+            .run_system_once(move |mut commands: Commands| {
+                reset_projectile(&mut commands.entity(entity), shot);
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn reset_projectiles_forget_their_last_shot() {
+        let mut world = World::new();
+        let old_owner = world.spawn_empty().id();
+        let owner = world.spawn_empty().id();
+        let projectile = world.spawn_empty().id();
+        reset(&mut world, projectile, shot(old_owner, CCD_SPEED_THRESHOLD * 2.0));
+        // Faded out near the end of its life and then laid as a mine
+        let mut entity = world.entity_mut(projectile);
+        entity.get_mut::<Sprite>().unwrap().color.set_alpha(0.1);
+        entity.get_mut::<Projectile>().unwrap().lifetime = f32::INFINITY;
+        entity.insert((Sticky { armed: true }, Sensor, RigidBody::Static, ProjectileSerial(3)));
+
+        reset(&mut world, projectile, shot(owner, CCD_SPEED_THRESHOLD / 2.0));
+        let entity = world.entity(projectile);
+        assert_eq!(entity.get::<Sprite>().unwrap().color.alpha(), 1.0);
+        let state = entity.get::<Projectile>().unwrap();
+        assert_eq!(state.owner, owner);
+        assert_eq!(state.lifetime, 2.0);
+        assert_eq!(state.max_lifetime, 2.0);
+        assert_eq!(state.spawn_position, Vec2::new(10.0, 20.0));
+        assert_eq!(entity.get::<RigidBody>(), Some(&RigidBody::Dynamic));
+        assert!(!entity.contains::<Sticky>());
+        assert!(!entity.contains::<Sensor>());
+        assert!(!entity.contains::<ProjectileSerial>());
+        // Too slow for continuous collision detection this time around
+        assert!(!entity.contains::<SweptCcd>());
+    }
+
+    fn spawn_projectiles(app: &mut App, count: usize) -> Vec<Entity> {
+        let owner = app.world_mut().spawn_empty().id();
+        let projectiles = (0..count).map(move |_| Projectile {
+            lifetime: 1.0,
+            max_lifetime: 1.0,
+            owner,
+            spawn_position: Vec2::ZERO,
+            damage: 1.0,
+            falloff: WeaponKind::Pistol.falloff(),
+            blast_radius: None,
+        });
+        app.world_mut().spawn_batch(projectiles).collect()
+    }
+
+    #[test]
+    fn projectiles_over_the_budget_are_removed_oldest_first() {
+        let mut app = App::new();
+        app.insert_resource(ProjectileBudget(512))
+            .add_systems(Update, enforce_projectile_budget);
+        let oldest = spawn_projectiles(&mut app, 400);
+        app.update();
+        // Everyone opens fire at once
+        let newest = spawn_projectiles(&mut app, 5000);
+        app.update();
+
+        let world = app.world_mut();
+        let live: Vec<Entity> = world
+            .query_filtered::<Entity, With<Projectile>>()
+            .iter(world)
+            .collect();
+        assert_eq!(live.len(), 512);
+        assert!(oldest.iter().all(|projectile| !live.contains(projectile)));
+        assert!(newest[newest.len() - 512..]
+            .iter()
+            .all(|projectile| live.contains(projectile)));
+    }
+
+    // Shoots a projectile as heavy as `kind`'s at a crate at rest, and returns how fast
+    // the crate moves away. Everything but the mass is the same for every kind.
+    fn crate_speed_after_hit(kind: WeaponKind) -> f32 {
+        let mut app = physics_app();
+        let world = app.world_mut();
+        let target = world
+            .spawn((
+                RigidBody::Dynamic,
+                Collider::rectangle(20.0, 20.0),
+                Transform::from_xyz(60.0, 0.0, 0.0),
+                layers::item(),
+            ))
+            .id();
+        let owner = world.spawn_empty().id();
+        let projectile = world.spawn_empty().id();
+        reset(
+            world,
+            projectile,
+            Shot {
+                transform: Transform::default(),
+                mass: Weapon::new(kind).projectile_mass,
+                gravity_scale: 0.0,
+                ..shot(owner, 400.0)
+            },
+        );
+        run_ticks(&mut app, 32);
+        app.world().get::<LinearVelocity>(target).unwrap().x
+    }
+
+    #[test]
+    fn heavier_projectiles_shove_harder() {
+        let light = crate_speed_after_hit(WeaponKind::Rifle);
+        let heavy = crate_speed_after_hit(WeaponKind::Grenade);
+        assert!(light > 0.0, "the crate wasn't hit");
+        assert!(heavy > light, "heavy {heavy} isn't faster than light {light}");
+    }
+
+    #[test]
+    fn fast_shots_dont_tunnel_through_thin_walls() {
+        let mut app = physics_app();
+        let world = app.world_mut();
+        // Much thinner than the distance the shot covers in a tick
+        world.spawn((
+            RigidBody::Static,
+            Collider::rectangle(2.0, 200.0),
+            Transform::from_xyz(100.0, 0.0, 0.0),
+            layers::world(),
+        ));
+        let owner = world.spawn_empty().id();
+        let projectile = world.spawn_empty().id();
+        reset(
+            world,
+            projectile,
+            Shot {
+                transform: Transform::default(),
+                gravity_scale: 0.0,
+                ..shot(owner, 3000.0)
+            },
+        );
+        assert!(world.entity(projectile).contains::<SweptCcd>());
+        run_ticks(&mut app, 16);
+        let position = app.world().get::<Position>(projectile).unwrap();
+        assert!(position.x < 100.0, "went through the wall to {}", position.x);
+    }
+
+    // A character holding a `kind` gun, with its trigger held down or not.
+    fn armed_character(world: &mut World, kind: WeaponKind, trigger: f32) -> Entity {
+        let mut fire = FireImpulse([0.0; 2]);
+        fire.press(WeaponSlot::Primary, trigger);
+        let character = world.spawn(fire).id();
+        world
+            .spawn((Gun, Weapon::new(kind), Spread::new(kind)))
+            .set_parent(character)
+            .id()
+    }
+
+    fn advance(world: &mut World, secs: f32) {
+        world
+            .resource_mut::<Time>()
+            .advance_by(std::time::Duration::from_secs_f32(secs));
+    }
+
+    #[test]
+    fn spread_only_recovers_once_firing_stops() {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        let held = armed_character(&mut world, WeaponKind::Rifle, 1.0);
+        let released = armed_character(&mut world, WeaponKind::Rifle, 0.0);
+        let cooling_down = armed_character(&mut world, WeaponKind::Rifle, 0.0);
+        world
+            .get_mut::<Weapon>(cooling_down)
+            .unwrap()
+            .consume_shot();
+        for gun in [held, released, cooling_down] {
+            let mut spread = world.get_mut::<Spread>(gun).unwrap();
+            spread.current = spread.max;
+        }
+        advance(&mut world, 0.05);
+        world.run_system_once(recover_spread).unwrap();
+
+        let spread = |world: &World, gun| world.get::<Spread>(gun).unwrap().clone();
+        let max = spread(&world, held).max;
+        assert_eq!(spread(&world, held).current, max);
+        assert_eq!(spread(&world, cooling_down).current, max);
+        assert!(spread(&world, released).current < max);
+    }
+
+    #[test]
+    fn barrels_only_cool_once_firing_stops() {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        let held = armed_character(&mut world, WeaponKind::Pistol, 1.0);
+        let released = armed_character(&mut world, WeaponKind::Pistol, 0.0);
+        let overheated = armed_character(&mut world, WeaponKind::Pistol, 1.0);
+        for gun in [held, released, overheated] {
+            let mut heat = Heat::new(WeaponKind::Pistol).unwrap();
+            heat.current = heat.max / 2.0;
+            world.entity_mut(gun).insert((heat, Sprite::default()));
+        }
+        world.get_mut::<Heat>(overheated).unwrap().overheated = true;
+        advance(&mut world, 0.1);
+        world.run_system_once(cool_guns).unwrap();
+
+        let heat = |world: &World, gun| world.get::<Heat>(gun).unwrap().current;
+        let start = Heat::new(WeaponKind::Pistol).unwrap().max / 2.0;
+        assert_eq!(heat(&world, held), start);
+        assert!(heat(&world, released) < start);
+        assert!(heat(&world, overheated) < start);
+    }
+
+    #[test]
+    fn projectiles_pass_through_each_other() {
+        let mut app = physics_app();
+        let world = app.world_mut();
+        let owner = world.spawn_empty().id();
+        let left = world.spawn_empty().id();
+        let right = world.spawn_empty().id();
+        reset(
+            world,
+            left,
+            Shot {
+                transform: Transform::from_xyz(-50.0, 0.0, 0.0),
+                gravity_scale: 0.0,
+                ..shot(owner, 200.0)
+            },
+        );
+        reset(
+            world,
+            right,
+            Shot {
+                transform: Transform::from_xyz(50.0, 0.0, 0.0),
+                gravity_scale: 0.0,
+                ..shot(owner, -200.0)
+            },
+        );
+        run_ticks(&mut app, 32);
+        let world = app.world();
+        assert_eq!(world.get::<LinearVelocity>(left).unwrap().0, Vec2::new(200.0, 0.0));
+        assert_eq!(world.get::<LinearVelocity>(right).unwrap().0, Vec2::new(-200.0, 0.0));
+        assert!(world.get::<Position>(left).unwrap().x > 0.0);
+        assert!(world.get::<Position>(right).unwrap().x < 0.0);
+    }
+
+    #[test]
+    fn fast_shots_use_continuous_collision_detection() {
+        let mut world = World::new();
+        let owner = world.spawn_empty().id();
+        let projectile = world.spawn_empty().id();
+        reset(&mut world, projectile, shot(owner, CCD_SPEED_THRESHOLD * 2.0));
+        assert!(world.entity(projectile).contains::<SweptCcd>());
+    }
+}